@@ -0,0 +1,24 @@
+#!/bin/bash
+# Build the escrow contracts of scenarios/escrow.toml for all platforms, next to the other bundled
+# contracts: the ink! contract with cargo-contract, and the Solidity contract with hardhat for EVM
+# and with solang for Wasm.
+set -euo pipefail
+
+CONTRACTS_PATH=$(dirname "$(realpath -s "${BASH_SOURCE[0]}")")
+
+for command in cargo-contract npx solang; do
+  if ! command -v "${command}" >/dev/null 2>&1; then
+    echo "${command} not found, see the README on how to install it" 1>&2
+    exit 1
+  fi
+done
+
+cd "${CONTRACTS_PATH}/ink/escrow"
+cargo contract build --release
+cp target/ink/escrow.contract ..
+
+cd "${CONTRACTS_PATH}/solidity"
+npx hardhat compile
+
+cd "${CONTRACTS_PATH}/solidity/wasm"
+solang compile --release --wasm-opt z --target polkadot ../contracts/Escrow.sol
@@ -0,0 +1,73 @@
+#![cfg_attr(not(feature = "std"), no_std, no_main)]
+
+#[ink::contract]
+mod escrow {
+    use ink::storage::Mapping;
+
+    #[ink(storage)]
+    #[derive(Default)]
+    pub struct Escrow {
+        deposits: Mapping<AccountId, Balance>,
+    }
+
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        InsufficientDeposit,
+        TransferFailed,
+    }
+
+    impl Escrow {
+        /// The value transferred with the instantiation is held by the contract, without being
+        /// deposited for any account.
+        #[ink(constructor, payable)]
+        pub fn new() -> Self {
+            Self {
+                deposits: Mapping::default(),
+            }
+        }
+
+        /// Deposit the value transferred with the call for the caller.
+        #[ink(message, payable)]
+        pub fn deposit(&mut self) {
+            let caller = self.env().caller();
+            let deposit = self.deposits.get(caller).unwrap_or(0);
+            self.deposits
+                .insert(caller, &(deposit + self.env().transferred_value()));
+        }
+
+        /// Withdraw the given amount of the deposit of the caller.
+        #[ink(message)]
+        pub fn withdraw(&mut self, amount: Balance) -> Result<(), Error> {
+            let caller = self.env().caller();
+            let deposit = self.deposits.get(caller).unwrap_or(0);
+            if deposit < amount {
+                return Err(Error::InsufficientDeposit);
+            }
+            self.deposits.insert(caller, &(deposit - amount));
+            self.env()
+                .transfer(caller, amount)
+                .map_err(|_| Error::TransferFailed)
+        }
+
+        #[ink(message)]
+        pub fn deposit_of(&self, account: AccountId) -> Balance {
+            self.deposits.get(account).unwrap_or(0)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[ink::test]
+        fn deposit_works() {
+            let accounts = ink::env::test::default_accounts::<ink::env::DefaultEnvironment>();
+            let mut escrow = Escrow::new();
+            ink::env::test::set_value_transferred::<ink::env::DefaultEnvironment>(1000);
+            escrow.deposit();
+            assert_eq!(escrow.deposit_of(accounts.alice), 1000);
+            assert_eq!(escrow.withdraw(2000), Err(Error::InsufficientDeposit));
+        }
+    }
+}
@@ -0,0 +1,22 @@
+pragma solidity ^0.8.0;
+
+contract Escrow {
+
+    mapping(address => uint256) private _deposits;
+
+    constructor() payable { }
+
+    function deposit() public payable {
+        _deposits[msg.sender] += msg.value;
+    }
+
+    function withdraw(uint256 amount) public {
+        require(_deposits[msg.sender] >= amount, "insufficient deposit");
+        _deposits[msg.sender] -= amount;
+        payable(msg.sender).transfer(amount);
+    }
+
+    function depositOf(address account) public view returns (uint256) {
+        return _deposits[account];
+    }
+}
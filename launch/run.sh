@@ -9,6 +9,7 @@ IMAGE="${NAME}:${VERSION}"
 BINARIES_DIR=""
 CONTRACTS_DIR=""
 CONFIGS_DIR=""
+RESULTS_DIR=""
 
 function echoerr() { echo "$@" 1>&2; }
 
@@ -21,6 +22,7 @@ OPTION
                       List of binaries being used depends on config provided. Default set of binaries is available within the image
  -t, --contracts-dir  (Optional) Path to directory that contains compiled smart contracts. Default set of compiled smart contracts is available within the image
  -u, --configs-dir    (Optional) Path to directory that contains zombienet config files. Default set of configs files is available within the image
+ -r, --results-dir    (Optional) Path to directory mounted at /usr/local/smart-bench/results, to exchange result files (--output-file, --compare) with the container
  -h, --help           Print this help message
 
 ARGUMENTS_TO_SMART_BENCH
@@ -33,6 +35,7 @@ ${SCRIPT_NAME} -- sol-wasm erc20 --instance-count 1 --call-count 10
 ${SCRIPT_NAME} --binaries-dir=./bin -- sol-wasm erc20 --instance-count 1 --call-count 10
 ${SCRIPT_NAME} --contracts-dir=../contracts -- sol-wasm erc20 --instance-count 1 --call-count 10
 ${SCRIPT_NAME} --configs-dir=./configs -- sol-wasm erc20 --instance-count 1 --call-count 10
+${SCRIPT_NAME} --results-dir=./results -- sol-wasm erc20 --instance-count 1 --call-count 10 --compare /usr/local/smart-bench/results/baseline.json
 
 EOF
 }
@@ -58,10 +61,14 @@ function parse_args {
          echoerr "CONTRACTS_DIR path=[${CONTRACTS_DIR}] doesn't exist"
          exit 2
       }
+      [ -z "${RESULTS_DIR}" ] || RESULTS_DIR=$(realpath -qe "${RESULTS_DIR}") || {
+         echoerr "RESULTS_DIR path=[${RESULTS_DIR}] doesn't exist"
+         exit 2
+      }
   }
 
   # shellcheck disable=SC2214
-  while getopts b:c:t:u:h:-: OPT; do
+  while getopts b:c:t:u:r:h:-: OPT; do
     # support long options: https://stackoverflow.com/a/28466267/519360
     if [ "$OPT" = "-" ]; then   # long option: reformulate OPT and OPTARG
       OPT="${OPTARG%%=*}"       # extract long option name
@@ -72,6 +79,7 @@ function parse_args {
       b | binaries-dir)         BINARIES_DIR="${OPTARG}";;
       t | contracts-dir)        CONTRACTS_DIR="${OPTARG}";;
       u | configs-dir)          CONFIGS_DIR="${OPTARG}";;
+      r | results-dir)          RESULTS_DIR="${OPTARG}";;
       h | help )                usage; exit 0;;
       ??* )                     echoerr "Illegal option --$OPT"; exit 2;;  # bad long option
       ? )                       exit 2 ;;  # bad short option (error reported via getopts)
@@ -88,6 +96,7 @@ container_dir="/usr/local"
 container_zombienet_configs="${container_dir}/smart-bench/config"
 container_contracts="${container_dir}/smart-bench/contracts"
 container_binaries="${container_dir}/smart-bench/bin"
+container_results="${container_dir}/smart-bench/results"
 
 volume_args=""
 if [ -n "${CONFIGS_DIR}" ]; then
@@ -102,6 +111,10 @@ if [ -n "${BINARIES_DIR}" ]; then
   volume_args="${volume_args} -v ${BINARIES_DIR}:${container_binaries}"
 fi
 
+if [ -n "${RESULTS_DIR}" ]; then
+  volume_args="${volume_args} -v ${RESULTS_DIR}:${container_results}"
+fi
+
 # shellcheck disable=SC2086
 (set -x; docker run --platform linux/amd64 --rm -t --init \
   ${volume_args} \
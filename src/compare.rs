@@ -0,0 +1,229 @@
+use crate::{
+    latency::Latency,
+    stats::{Summary, Utilisation},
+    Cli,
+};
+use color_eyre::{eyre, Section as _};
+use serde::Deserialize;
+use std::{io::Write, path::Path};
+
+/// The parts of a previous JSON report (`--output-format json`) a benchmark run is compared to.
+/// Results missing from older reports are not compared.
+#[derive(Debug, Deserialize)]
+struct Baseline {
+    parameters: serde_json::Map<String, serde_json::Value>,
+    summary: BaselineSummary,
+}
+
+#[derive(Debug, Deserialize)]
+struct BaselineSummary {
+    stps: Option<f64>,
+    block_utilisation: Option<Utilisation>,
+    in_block_latency: Option<Latency>,
+    finalized_latency: Option<Latency>,
+}
+
+impl Baseline {
+    fn load(path: &Path) -> color_eyre::Result<Self> {
+        let reader = std::fs::File::open(path)
+            .with_note(|| format!("Error opening baseline {}", path.display()))?;
+        serde_json::from_reader(std::io::BufReader::new(reader))
+            .with_note(|| format!("Error parsing baseline {}", path.display()))
+    }
+
+    /// The run parameters which differ from the baseline, apart from the url of the node.
+    fn differing_parameters(&self, cli: &Cli) -> color_eyre::Result<Vec<String>> {
+        let current = match serde_json::to_value(cli)? {
+            serde_json::Value::Object(current) => current,
+            _ => return Err(eyre::eyre!("Run parameters should serialize to an object")),
+        };
+        Ok(current
+            .iter()
+            .filter(|(name, value)| *name != "url" && self.parameters.get(*name) != Some(value))
+            .map(|(name, _)| name.clone())
+            .collect())
+    }
+}
+
+/// Whether an increase or a decrease of a compared value is a regression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Better {
+    Higher,
+    Lower,
+}
+
+/// A result of the benchmark run compared to the baseline.
+#[derive(Debug)]
+struct Delta {
+    name: &'static str,
+    unit: &'static str,
+    baseline: f64,
+    current: f64,
+    /// `None` if the value is only reported, but never counts as regression.
+    better: Option<Better>,
+}
+
+impl Delta {
+    /// Change relative to the baseline in percent, `None` if the baseline is zero.
+    fn change(&self) -> Option<f64> {
+        (self.baseline != 0.0).then(|| (self.current - self.baseline) / self.baseline * 100.0)
+    }
+
+    /// Whether the value got worse by more than `threshold` percent.
+    fn regressed(&self, threshold: f64) -> bool {
+        match (self.better, self.change()) {
+            (Some(Better::Higher), Some(change)) => change < -threshold,
+            (Some(Better::Lower), Some(change)) => change > threshold,
+            _ => false,
+        }
+    }
+}
+
+/// The results of a benchmark run compared to a baseline.
+#[derive(Debug)]
+struct Comparison {
+    deltas: Vec<Delta>,
+}
+
+impl Comparison {
+    fn new(baseline: &BaselineSummary, summary: &Summary) -> Self {
+        let mut deltas = Vec::new();
+        let mut push = |name, unit, values: Option<(f64, f64)>, better| {
+            if let Some((baseline, current)) = values {
+                deltas.push(Delta {
+                    name,
+                    unit,
+                    baseline,
+                    current,
+                    better,
+                })
+            }
+        };
+
+        push(
+            "sTPS",
+            "",
+            baseline.stps.zip(summary.stps),
+            Some(Better::Higher),
+        );
+
+        let utilisation = baseline.block_utilisation.zip(summary.block_utilisation);
+        let percent = |value: fn(&Utilisation) -> f64| {
+            utilisation.map(|(b, c)| (value(&b) * 100.0, value(&c) * 100.0))
+        };
+        push("Block RefTime", "%", percent(|u| u.ref_time), None);
+        push("Block ProofSize", "%", percent(|u| u.proof_size), None);
+        push("Block PoV", "%", percent(|u| u.pov), None);
+
+        for (name, latency) in [
+            (
+                ["Latency In Block p50", "Latency In Block p90"],
+                baseline.in_block_latency.zip(summary.in_block_latency),
+            ),
+            (
+                ["Latency Finalized p50", "Latency Finalized p90"],
+                baseline.finalized_latency.zip(summary.finalized_latency),
+            ),
+        ] {
+            push(
+                name[0],
+                "ms",
+                latency.map(|(b, c)| (b.p50_ms, c.p50_ms)),
+                None,
+            );
+            push(
+                name[1],
+                "ms",
+                latency.map(|(b, c)| (b.p90_ms, c.p90_ms)),
+                Some(Better::Lower),
+            );
+        }
+
+        Self { deltas }
+    }
+
+    fn write_text(&self, w: &mut impl Write) -> std::io::Result<()> {
+        for delta in &self.deltas {
+            write!(
+                w,
+                "{}: {:.2}{unit} -> {:.2}{unit}",
+                delta.name,
+                delta.baseline,
+                delta.current,
+                unit = delta.unit
+            )?;
+            match delta.change() {
+                Some(change) => writeln!(w, " ({change:+.2}%)")?,
+                None => writeln!(w)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Fail if any of the compared values regressed by more than `threshold` percent.
+    fn check(&self, threshold: f64) -> color_eyre::Result<()> {
+        let regressions = self
+            .deltas
+            .iter()
+            .filter(|delta| delta.regressed(threshold))
+            .filter_map(|delta| Some(format!("{} {:+.2}%", delta.name, delta.change()?)))
+            .collect::<Vec<_>>();
+        if regressions.is_empty() {
+            Ok(())
+        } else {
+            Err(eyre::eyre!(
+                "Regression of more than {threshold}% compared to the baseline: {}",
+                regressions.join(", ")
+            ))
+        }
+    }
+}
+
+/// Compare the results of the benchmark run to the baseline at `path`, writing the deltas to `w`.
+/// Fails if the sTPS or the p90 latency regressed by more than `--regression-threshold` percent.
+pub fn compare(
+    cli: &Cli,
+    path: &Path,
+    summary: &Summary,
+    w: &mut impl Write,
+) -> color_eyre::Result<()> {
+    let baseline = Baseline::load(path)?;
+    let differing = baseline.differing_parameters(cli)?;
+    if !differing.is_empty() {
+        tracing::warn!(
+            "Run parameters differ from the baseline: {}",
+            differing.join(", ")
+        );
+    }
+
+    let comparison = Comparison::new(&baseline.summary, summary);
+    writeln!(w, "\nComparison with baseline {}:", path.display())?;
+    comparison.write_text(w)?;
+    comparison.check(cli.regression_threshold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(baseline: f64, current: f64, better: Option<Better>) -> Delta {
+        Delta {
+            name: "test",
+            unit: "",
+            baseline,
+            current,
+            better,
+        }
+    }
+
+    #[test]
+    fn regression_beyond_threshold() {
+        assert!(delta(100.0, 89.0, Some(Better::Higher)).regressed(10.0));
+        assert!(!delta(100.0, 91.0, Some(Better::Higher)).regressed(10.0));
+        assert!(!delta(100.0, 150.0, Some(Better::Higher)).regressed(10.0));
+
+        assert!(delta(1000.0, 1200.0, Some(Better::Lower)).regressed(10.0));
+        assert!(!delta(1000.0, 1050.0, Some(Better::Lower)).regressed(10.0));
+        assert!(!delta(1000.0, 500.0, Some(Better::Lower)).regressed(10.0));
+    }
+}
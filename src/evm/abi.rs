@@ -0,0 +1,95 @@
+use super::keyring;
+use color_eyre::{eyre, Section as _};
+use serde_json::Value;
+use web3::{
+    ethabi::{
+        token::{LenientTokenizer, Tokenizer as _},
+        Contract, Function, Param, ParamType, Token,
+    },
+    signing::Key as _,
+};
+
+/// The function of the contract with the given name, or signature such as
+/// `transfer(address,uint256)` to select one of several overloaded functions.
+pub fn function<'a>(contract: &'a Contract, signature: &str) -> color_eyre::Result<&'a Function> {
+    let Some((name, _)) = signature.split_once('(') else {
+        return Ok(contract.function(signature)?);
+    };
+    let signature = signature.replace(' ', "");
+    contract
+        .functions_by_name(name)?
+        .iter()
+        .find(|function| {
+            let params = function
+                .inputs
+                .iter()
+                .map(|param| param.kind.to_string())
+                .collect::<Vec<_>>();
+            format!("{name}({})", params.join(",")) == signature
+        })
+        .ok_or_else(|| eyre::eyre!("No function {signature}"))
+}
+
+/// Tokens of the argument values given at runtime, e.g. in a scenario, for the given parameters.
+pub fn tokenize(params: &[Param], args: &[Value]) -> color_eyre::Result<Vec<Token>> {
+    if params.len() != args.len() {
+        return Err(eyre::eyre!(
+            "Expected {} arguments ({}), got {}",
+            params.len(),
+            params
+                .iter()
+                .map(|param| param.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            args.len()
+        ));
+    }
+    params
+        .iter()
+        .zip(args)
+        .map(|(param, value)| {
+            token(&param.kind, value).with_note(|| format!("Argument {}", param.name))
+        })
+        .collect()
+}
+
+fn token(kind: &ParamType, value: &Value) -> color_eyre::Result<Token> {
+    let tokens = |kinds: &mut dyn Iterator<Item = &ParamType>, items: &[Value]| {
+        kinds
+            .zip(items)
+            .map(|(kind, item)| token(kind, item))
+            .collect::<color_eyre::Result<Vec<_>>>()
+    };
+    match (kind, value) {
+        (ParamType::Array(inner), Value::Array(items)) => Ok(Token::Array(tokens(
+            &mut std::iter::repeat(inner.as_ref()),
+            items,
+        )?)),
+        (ParamType::FixedArray(inner, len), Value::Array(items)) if items.len() == *len => Ok(
+            Token::FixedArray(tokens(&mut std::iter::repeat(inner.as_ref()), items)?),
+        ),
+        (ParamType::Tuple(kinds), Value::Array(items)) if items.len() == kinds.len() => {
+            Ok(Token::Tuple(tokens(&mut kinds.iter(), items)?))
+        }
+        (ParamType::Address, Value::String(s)) => match s.to_lowercase().as_str() {
+            "alith" => Ok(Token::Address((&keyring::alith()).address())),
+            "balthazar" => Ok(Token::Address((&keyring::balthazar()).address())),
+            _ => tokenize_str(kind, s.trim_start_matches("0x")),
+        },
+        (ParamType::Bytes | ParamType::FixedBytes(_), Value::String(s)) => {
+            tokenize_str(kind, s.trim_start_matches("0x"))
+        }
+        (ParamType::Int(_) | ParamType::Uint(_), Value::String(s)) => {
+            tokenize_str(kind, &s.replace('_', ""))
+        }
+        (_, Value::String(s)) => tokenize_str(kind, s),
+        (_, Value::Number(n)) => tokenize_str(kind, &n.to_string()),
+        (_, Value::Bool(b)) => tokenize_str(kind, &b.to_string()),
+        _ => Err(eyre::eyre!("Expected {kind} value, got {value}")),
+    }
+}
+
+fn tokenize_str(kind: &ParamType, value: &str) -> color_eyre::Result<Token> {
+    LenientTokenizer::tokenize(kind, value)
+        .map_err(|e| eyre::eyre!("Invalid {kind} value {value}: {e}"))
+}
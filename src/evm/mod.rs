@@ -1,19 +1,158 @@
+mod abi;
 mod runner;
 mod transaction;
 mod xts;
 
 use crate::{
     evm::{runner::MoonbeamRunner, xts::MoonbeamApi},
+    finality::FinalizedBlocks,
+    find_max::FindMax,
+    report::Environment,
+    scenario::{Scenario, ScenarioContract, Workload},
+    schedule::Schedule,
     Cli, Contract,
 };
+use color_eyre::{eyre, Section as _};
 use web3::{contract::tokens::Tokenize, signing::Key, types::U256};
 
+/// Each of the `--signers` is funded with 100 units of a chain with 18 decimals.
+const SIGNER_FUNDS_DECIMALS: usize = 20;
+
 pub async fn exec(cli: &Cli) -> color_eyre::Result<()> {
+    if cli.contract_bundle.is_some() {
+        return Err(eyre::eyre!(
+            "--contract-bundle is only supported for Wasm contracts"
+        ))
+        .suggestion("Provide the artifact of an EVM contract with --artifact");
+    }
+    if cli.upload_once {
+        return Err(eyre::eyre!(
+            "--upload-once is only supported for Wasm contracts"
+        ))
+        .note("EVM contracts are deployed with their code");
+    }
+    if cli.storage_deposit_limit.is_some() {
+        return Err(eyre::eyre!(
+            "--storage-deposit-limit is only supported for Wasm contracts"
+        ))
+        .note("EVM contracts don't hold storage deposits");
+    }
+
     let api = MoonbeamApi::new(&cli.url).await?;
 
-    let mut runner = MoonbeamRunner::new(cli.url.to_string(), keyring::alith(), api);
+    let mut runner = MoonbeamRunner::new(
+        cli.url.to_string(),
+        keyring::alith(),
+        api,
+        cli.finality,
+        cli.deployments,
+    );
+
+    if cli.signers > 1 {
+        let signers = (0..cli.signers)
+            .map(|index| keyring::derive(&cli.signer_seed, index))
+            .collect();
+        runner
+            .fund_call_signers(signers, U256::exp10(SIGNER_FUNDS_DECIMALS))
+            .await?;
+    }
+
+    if let Some(scenario) = &cli.scenario {
+        let scenario = Scenario::load(scenario)?;
+        prepare_scenario_contracts(cli, scenario.contracts(&cli.chain)?, &mut runner).await?;
+    } else if let Some(artifact) = &cli.artifact {
+        let contract = ScenarioContract::from_cli(artifact, cli)?;
+        prepare_scenario_contracts(cli, &[contract], &mut runner).await?;
+    } else {
+        prepare_contracts(cli, &mut runner).await?;
+    }
+
+    let environment = Environment::new(cli, runner.compiler_version()).await?;
+    let finalized_blocks = FinalizedBlocks::subscribe(&cli.url).await?;
+    if cli.deployments {
+        let result = runner
+            .run_deployments(finalized_blocks.clone(), cli.rate, cli.max_in_flight)
+            .await?;
+        return crate::print_block_info(cli, &environment, &finalized_blocks, None, result).await;
+    }
+    if cli.find_max {
+        let mut find_max = FindMax::new(cli)?;
+        while let Some(rate) = find_max.next_rate() {
+            let schedule = Schedule::from_cli(cli, runner.workloads())?;
+            let result = runner
+                .run(
+                    finalized_blocks.clone(),
+                    schedule,
+                    Some(rate),
+                    cli.max_in_flight,
+                    false,
+                )
+                .await?;
+            find_max.record(rate, result).await?;
+        }
+        return find_max.write_results(cli, &environment);
+    }
+    let schedule = Schedule::from_cli(cli, runner.workloads())?;
+    let result = runner
+        .run(
+            finalized_blocks.clone(),
+            schedule,
+            cli.rate,
+            cli.max_in_flight,
+            cli.pre_sign,
+        )
+        .await?;
+    crate::print_block_info(cli, &environment, &finalized_blocks, None, result).await?;
+
+    Ok(())
+}
+
+/// Prepare the contracts of a scenario, encoding the constructor and function arguments with the
+/// contract ABI.
+async fn prepare_scenario_contracts(
+    cli: &Cli,
+    contracts: &[ScenarioContract],
+    runner: &mut MoonbeamRunner,
+) -> color_eyre::Result<()> {
+    for contract in contracts {
+        let workload = contract.workload(cli)?;
+        let artifact = MoonbeamRunner::load_artifact(&contract.contract)?;
+        let contract_abi = MoonbeamRunner::contract_abi(&artifact)?;
+        let ctor_inputs = contract_abi
+            .constructor()
+            .map(|constructor| constructor.inputs.as_slice())
+            .unwrap_or_default();
+        let ctor_params =
+            abi::tokenize(ctor_inputs, &contract.constructor_args).note("Constructor arguments")?;
+        let function = abi::function(&contract_abi, &contract.message)
+            .with_note(|| format!("Function '{}' not found", contract.message))?;
+        let call_params = (0..workload.instance_count)
+            .map(|instance| abi::tokenize(&function.inputs, &contract.args_for_instance(instance)))
+            .collect::<color_eyre::Result<Vec<_>>>()?;
+        let mut call_params = call_params.into_iter();
+        let create_call_params = || {
+            call_params
+                .next()
+                .expect("call parameters are tokenized for each instance")
+        };
+        runner
+            .prepare_contract(
+                contract.label(),
+                &contract.contract,
+                workload,
+                &ctor_params,
+                &contract.message,
+                create_call_params,
+            )
+            .await?;
+    }
+    Ok(())
+}
 
+async fn prepare_contracts(cli: &Cli, runner: &mut MoonbeamRunner) -> color_eyre::Result<()> {
+    let workload = Workload::from_cli(cli)?;
     for contract in &cli.contracts {
+        let label = contract.to_string();
         match contract {
             Contract::Erc20 => {
                 let transfer_to = (&keyring::balthazar()).address();
@@ -21,8 +160,9 @@ pub async fn exec(cli: &Cli) -> color_eyre::Result<()> {
                 let transfer_params = || (transfer_to, 1000u32).into_tokens();
                 runner
                     .prepare_contract(
+                        &label,
                         "BenchERC20",
-                        cli.instance_count,
+                        workload,
                         &ctor_params,
                         "transfer",
                         &transfer_params,
@@ -34,8 +174,9 @@ pub async fn exec(cli: &Cli) -> color_eyre::Result<()> {
                 let flip_params = || Vec::new();
                 runner
                     .prepare_contract(
+                        &label,
                         "flipper",
-                        cli.instance_count,
+                        workload,
                         &ctor_params,
                         "flip",
                         &flip_params,
@@ -47,8 +188,9 @@ pub async fn exec(cli: &Cli) -> color_eyre::Result<()> {
                 let inc_params = || (1u32,).into_tokens();
                 runner
                     .prepare_contract(
+                        &label,
                         "incrementer",
-                        cli.instance_count,
+                        workload,
                         &ctor_params,
                         "inc",
                         inc_params,
@@ -65,8 +207,9 @@ pub async fn exec(cli: &Cli) -> color_eyre::Result<()> {
                 };
                 runner
                     .prepare_contract(
+                        &label,
                         "BenchERC721",
-                        cli.instance_count,
+                        workload,
                         &ctor_params,
                         "mint",
                         mint_params,
@@ -78,8 +221,9 @@ pub async fn exec(cli: &Cli) -> color_eyre::Result<()> {
                 let create_params = || (U256::from(1_000_000),).into_tokens();
                 runner
                     .prepare_contract(
+                        &label,
                         "BenchERC1155",
-                        cli.instance_count,
+                        workload,
                         &ctor_params,
                         "create",
                         create_params,
@@ -91,8 +235,9 @@ pub async fn exec(cli: &Cli) -> color_eyre::Result<()> {
                 let call_params = || (1000i32,).into_tokens();
                 runner
                     .prepare_contract(
+                        &label,
                         "Computation",
-                        cli.instance_count,
+                        workload,
                         &ctor_params,
                         "oddProduct",
                         call_params,
@@ -104,8 +249,9 @@ pub async fn exec(cli: &Cli) -> color_eyre::Result<()> {
                 let call_params = || (1000i32,).into_tokens();
                 runner
                     .prepare_contract(
+                        &label,
                         "Computation",
-                        cli.instance_count,
+                        workload,
                         &ctor_params,
                         "triangleNumber",
                         call_params,
@@ -118,8 +264,9 @@ pub async fn exec(cli: &Cli) -> color_eyre::Result<()> {
                 let call_params = || (address, 10).into_tokens();
                 runner
                     .prepare_contract(
+                        &label,
                         "Storage",
-                        cli.instance_count,
+                        workload,
                         &ctor_params,
                         "read",
                         call_params,
@@ -132,8 +279,9 @@ pub async fn exec(cli: &Cli) -> color_eyre::Result<()> {
                 let call_params = || (address, 10).into_tokens();
                 runner
                     .prepare_contract(
+                        &label,
                         "Storage",
-                        cli.instance_count,
+                        workload,
                         &ctor_params,
                         "write",
                         call_params,
@@ -146,8 +294,9 @@ pub async fn exec(cli: &Cli) -> color_eyre::Result<()> {
                 let call_params = || (address, 10).into_tokens();
                 runner
                     .prepare_contract(
+                        &label,
                         "Storage",
-                        cli.instance_count,
+                        workload,
                         &ctor_params,
                         "readWrite",
                         call_params,
@@ -156,10 +305,6 @@ pub async fn exec(cli: &Cli) -> color_eyre::Result<()> {
             }
         }
     }
-
-    let result = runner.run(cli.call_count).await?;
-    crate::print_block_info(result).await?;
-
     Ok(())
 }
 
@@ -176,4 +321,11 @@ mod keyring {
         SecretKey::from_str("8075991ce870b93a8870eca0c0f91913d12f47948ca0fd25b49c6fa7cdbeee8b")
             .unwrap()
     }
+
+    /// The account with the given index derived from the `--signer-seed`, the keccak256 hash of
+    /// `{seed}//{index}`.
+    pub fn derive(seed: &str, index: u32) -> SecretKey {
+        let secret = web3::signing::keccak256(format!("{seed}//{index}").as_bytes());
+        SecretKey::from_slice(&secret).expect("a keccak256 hash is a valid secret key")
+    }
 }
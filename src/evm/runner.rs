@@ -1,4 +1,9 @@
-use std::collections::HashSet;
+use std::{
+    collections::HashSet,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
 use super::xts::{
     api::{
@@ -10,42 +15,78 @@ use super::xts::{
     },
     MoonbeamApi,
 };
-use crate::BlockInfo;
+use crate::{
+    finality::FinalizedBlocks,
+    outcome::{self, CallOutcome, RevertReason},
+    rate::Rate,
+    scenario::Workload,
+    schedule::Schedule,
+    stats::{BlockDetails, IncludedCall, PendingCalls, SubmittedCall},
+    value::CallValue,
+    BlockInfo, Finality,
+};
 use color_eyre::{eyre, Section as _};
-use futures::TryStream;
+use futures::{future, StreamExt as _, TryStream, TryStreamExt as _};
 use impl_serde::serialize::from_hex;
 use secp256k1::SecretKey;
-use subxt::{OnlineClient, PolkadotConfig as DefaultConfig};
 use web3::{
     ethabi::Token,
     signing::{Key, SecretKeyRef},
-    types::{Address, U256},
+    types::{Address, Bytes, H256, U256},
 };
 
+/// How long to wait for the transfers funding the `--signers` to be included.
+const FUNDING_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The maximum number of reverted calls per block replayed to decode the reason of the revert.
+const MAX_REVERT_REASONS: usize = 10;
+
 pub struct MoonbeamRunner {
     url: String,
     pub api: MoonbeamApi,
     signer: SecretKey,
     address: Address,
-    calls: Vec<(String, Vec<RunnerCall>)>,
+    /// The accounts the calls are spread across, the `signer` if there are none.
+    call_signers: Vec<SecretKey>,
+    contracts: Vec<PreparedContract>,
+    compiler_versions: Vec<String>,
+    finality: Finality,
+    /// Benchmark the deployment of the contracts rather than calls to them, see `--deployments`.
+    measure_deployments: bool,
+    deployments: Vec<PreparedDeployment>,
 }
 
 impl MoonbeamRunner {
-    pub fn new(url: String, signer: SecretKey, api: MoonbeamApi) -> Self {
+    pub fn new(
+        url: String,
+        signer: SecretKey,
+        api: MoonbeamApi,
+        finality: Finality,
+        measure_deployments: bool,
+    ) -> Self {
         let address = Key::address(&SecretKeyRef::from(&signer));
         Self {
             url,
             signer,
             api,
             address,
-            calls: Vec::new(),
+            call_signers: Vec::new(),
+            contracts: Vec::new(),
+            compiler_versions: Vec::new(),
+            finality,
+            measure_deployments,
+            deployments: Vec::new(),
         }
     }
 
+    /// Deploy instances of contract, and build calls for benchmarking. The calls are reported
+    /// under the given `label`. With `--deployments`, the instances are only deployed by
+    /// [`Self::run_deployments`].
     pub async fn prepare_contract<F>(
         &mut self,
+        label: &str,
         name: &str,
-        instance_count: u32,
+        workload: Workload,
         ctor_params: &[Token],
         call_name: &str,
         mut create_call_params: F,
@@ -53,46 +94,72 @@ impl MoonbeamRunner {
     where
         F: FnMut() -> Vec<Token>,
     {
-        print!("Preparing {name}...");
+        eprint!("Preparing {name}...");
 
-        let root = std::env::var("CARGO_MANIFEST_DIR")?;
-        let metadata_path =
-            format!("{root}/contracts/solidity/evm/contracts/{name}.sol/{name}.json");
-
-        let metadata_reader = std::fs::File::open(metadata_path)?;
-        let json: serde_json::Map<String, serde_json::Value> =
-            serde_json::from_reader(metadata_reader)?;
+        let json = Self::load_artifact(name)?;
+        // hardhat artifacts contain the bytecode, foundry artifacts an object with the bytecode
         let bytecode = json["bytecode"]
             .as_str()
+            .or_else(|| json["bytecode"]["object"].as_str())
             .ok_or_else(|| eyre::eyre!("Bytecode should be a string"))?;
         let code = from_hex(bytecode).note("Error decoding hex bytecode")?;
-        let abi = json["abi"].clone();
-        let contract: web3::ethabi::Contract = serde_json::from_value(abi)?;
+        if let Some(compiler_version) = Self::solc_version(name, &json)? {
+            if !self.compiler_versions.contains(&compiler_version) {
+                self.compiler_versions.push(compiler_version);
+            }
+        }
+        let contract = Self::contract_abi(&json)?;
         let constructor = contract
             .constructor()
             .ok_or_else(|| eyre::eyre!("No constructor for contract found"))?;
 
-        println!("{}KiB", code.len() / 1024);
+        eprintln!("{}KiB", code.len() / 1024);
 
         let data = constructor.encode_input(code.into(), ctor_params)?;
 
-        let contract_accounts = self.exec_deploy(&data, instance_count).await?;
+        let gas_limit = self
+            .api
+            .estimate_gas(
+                self.address,
+                None,
+                workload.constructor_value.for_call(0).into(),
+                &data,
+            )
+            .await
+            .note("Error estimating gas")?;
+        let deployment = PreparedDeployment {
+            label: label.to_string(),
+            value: workload.constructor_value,
+            data,
+            gas_limit,
+            instance_count: workload.instance_count,
+        };
+        if self.measure_deployments {
+            self.deployments.push(deployment);
+            return Ok(());
+        }
+        let contract_accounts = self.exec_deploy(&deployment).await?;
 
-        println!("Instantiated {} {name} contracts", contract_accounts.len());
+        eprintln!("Instantiated {} {name} contracts", contract_accounts.len());
 
-        let call = contract
-            .function(call_name)
+        let call = super::abi::function(&contract, call_name)
             .with_note(|| format!("Call '{call_name}' not found for {name}"))?;
 
+        // the calls are sent from the `--signers`, so gas is estimated for the first of them, in
+        // case the contract depends on the sender or its balance
+        let caller = self.call_signers.first().map_or(self.address, |signer| {
+            Key::address(&SecretKeyRef::from(signer))
+        });
         let mut calls = Vec::new();
-        for contract in contract_accounts {
+        for (instance, contract) in contract_accounts.into_iter().enumerate() {
             let call_params = create_call_params();
             let data = call
                 .encode_input(&call_params)
                 .note("Error encoding contract call input")?;
+            let value = workload.value.for_call(instance).into();
             let gas_limit = self
                 .api
-                .estimate_gas(self.address, Some(contract), &data)
+                .estimate_gas(caller, Some(contract), value, &data)
                 .await
                 .note("Error estimating gas")?;
             calls.push(RunnerCall {
@@ -102,32 +169,149 @@ impl MoonbeamRunner {
                 gas_limit,
             })
         }
-        self.calls.push((name.to_string(), calls));
+        self.contracts.push(PreparedContract {
+            label: label.to_string(),
+            call_count: workload.call_count,
+            calls,
+            value: workload.value,
+        });
 
         Ok(())
     }
 
+    /// Fund the given accounts with `value` from the signer, and spread the calls across them.
+    pub async fn fund_call_signers(
+        &mut self,
+        signers: Vec<SecretKey>,
+        value: U256,
+    ) -> color_eyre::Result<()> {
+        eprintln!("Funding {} signers...", signers.len());
+        let mut nonce = self.api.fetch_nonce(self.address).await?;
+        let gas_price = self.api.get_gas_price().await.note("Error getting gas")?;
+        let mut tx_hashes = Vec::new();
+        for signer in &signers {
+            let to = Key::address(&SecretKeyRef::from(signer));
+            let tx_hash = self
+                .api
+                .transfer(to, value, &self.signer, nonce, gas_price)
+                .await?;
+            tx_hashes.push(tx_hash);
+            nonce += 1.into();
+        }
+
+        let deadline = Instant::now() + FUNDING_TIMEOUT;
+        for tx_hash in tx_hashes {
+            let receipt = loop {
+                if let Some(receipt) = self.api.transaction_receipt(tx_hash).await? {
+                    break receipt;
+                }
+                if Instant::now() >= deadline {
+                    return Err(eyre::eyre!("Timed out funding signers"))
+                        .with_note(|| format!("Transfer {tx_hash:?} was not included"));
+                }
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            };
+            if receipt.status != Some(1.into()) {
+                return Err(eyre::eyre!("Transfer {tx_hash:?} funding a signer failed"));
+            }
+        }
+        self.call_signers = signers;
+        Ok(())
+    }
+
+    /// Load the artifact of the contract, with its bytecode and ABI.
+    pub fn load_artifact(
+        name: &str,
+    ) -> color_eyre::Result<serde_json::Map<String, serde_json::Value>> {
+        let metadata_path = Self::artifact_path(name)?;
+        let metadata_reader = std::fs::File::open(&metadata_path)
+            .with_note(|| format!("Error reading artifact {}", metadata_path.display()))
+            .suggestion("Contracts which are not committed compiled, like escrow, have to be built first, e.g. with contracts/build_escrow.sh")?;
+        let json = serde_json::from_reader(metadata_reader)?;
+        Ok(json)
+    }
+
+    /// The path of the hardhat artifact of a contract in `contracts/solidity/evm`, relative to the
+    /// root of the repository, or `name` itself if it is the path of a hardhat or foundry `.json`
+    /// artifact.
+    fn artifact_path(name: &str) -> color_eyre::Result<PathBuf> {
+        if name.ends_with(".json") {
+            return Ok(name.into());
+        }
+        let root = std::env::var("CARGO_MANIFEST_DIR")?;
+        Ok(format!("{root}/contracts/solidity/evm/contracts/{name}.sol/{name}.json").into())
+    }
+
+    /// The ABI of the contract from its artifact.
+    pub fn contract_abi(
+        artifact: &serde_json::Map<String, serde_json::Value>,
+    ) -> color_eyre::Result<web3::ethabi::Contract> {
+        let abi = artifact
+            .get("abi")
+            .ok_or_else(|| eyre::eyre!("Artifact has no ABI"))?;
+        Ok(serde_json::from_value(abi.clone())?)
+    }
+
+    /// The compiler(s) of the prepared contracts, as found in the hardhat build info.
+    pub fn compiler_version(&self) -> Option<String> {
+        (!self.compiler_versions.is_empty()).then(|| self.compiler_versions.join("+"))
+    }
+
+    /// Read the solc version from the metadata of a foundry artifact, or else from the build info
+    /// referenced by the hardhat debug file next to the artifact, if there is one.
+    fn solc_version(
+        name: &str,
+        artifact: &serde_json::Map<String, serde_json::Value>,
+    ) -> color_eyre::Result<Option<String>> {
+        if let Some(solc_version) = artifact
+            .get("metadata")
+            .and_then(|metadata| metadata["compiler"]["version"].as_str())
+        {
+            return Ok(Some(format!("solc {solc_version}")));
+        }
+        let dbg_path = Self::artifact_path(name)?.with_extension("dbg.json");
+        if !dbg_path.exists() {
+            return Ok(None);
+        }
+        let dbg_reader = std::fs::File::open(&dbg_path)?;
+        let dbg: serde_json::Value = serde_json::from_reader(dbg_reader)?;
+        let build_info = dbg["buildInfo"]
+            .as_str()
+            .ok_or_else(|| eyre::eyre!("buildInfo should be a string"))?;
+        let contract_dir = dbg_path.parent().unwrap_or_else(|| Path::new("."));
+        let build_info_reader = std::fs::File::open(contract_dir.join(build_info))?;
+        let build_info: serde_json::Value = serde_json::from_reader(build_info_reader)?;
+        let solc_version = build_info["solcVersion"]
+            .as_str()
+            .ok_or_else(|| eyre::eyre!("solcVersion should be a string"))?;
+        Ok(Some(format!("solc {solc_version}")))
+    }
+
     async fn exec_deploy(
         &self,
-        data: &[u8],
-        instance_count: u32,
+        deployment: &PreparedDeployment,
     ) -> color_eyre::Result<Vec<Address>> {
+        let instance_count = deployment.instance_count;
         let mut nonce = self.api.fetch_nonce(self.address).await?;
-        let mut block_sub = self.api.client().blocks().subscribe_best().await?;
-
-        let gas = self
-            .api
-            .estimate_gas(self.address, None, &data)
-            .await
-            .note("Error estimating gas")?;
+        let mut block_sub = match self.finality {
+            Finality::Best => self.api.client().blocks().subscribe_best().await?,
+            Finality::Finalized => self.api.client().blocks().subscribe_finalized().await?,
+        };
 
         let gas_price = self.api.get_gas_price().await.note("Error getting gas")?;
 
         let mut tx_hashes = HashSet::new();
-        for _ in 0..instance_count {
+        for instance in 0..instance_count {
             let tx_hash = self
                 .api
-                .deploy(data, &self.signer, nonce, gas, gas_price)
+                .deploy(
+                    &deployment.data,
+                    &self.signer,
+                    nonce,
+                    deployment.value.for_call(instance as usize).into(),
+                    deployment.gas_limit,
+                    gas_price,
+                )
                 .await?;
             tx_hashes.insert(tx_hash);
             nonce += 1.into();
@@ -208,12 +392,19 @@ impl MoonbeamRunner {
     ///
     /// for given block, ethereum transaction hash can be retrieved
     /// from events of type ethereum.Executed
+    ///
+    /// the hashes of contract calls are returned, or of contract deployments with `deployments`,
+    /// with how they ended
     async fn get_block_details(
-        client: OnlineClient<DefaultConfig>,
+        &self,
         block_hash: sp_core::H256,
-    ) -> color_eyre::Result<(u64, Vec<sp_core::H256>)> {
+        deployments: bool,
+    ) -> color_eyre::Result<BlockDetails> {
+        let client = self.api.client();
         let block = client.blocks().at(block_hash).await?;
-        let mut tx_hashes = Vec::new();
+        let mut calls = Vec::new();
+        let mut revert_reasons = 0;
+        let mut eth_parent_hash = None;
         let extrinsics_details = block
             .extrinsics()
             .await?
@@ -223,15 +414,62 @@ impl MoonbeamRunner {
         for extrinsic_detail in extrinsics_details {
             if let Some(Transact { transaction }) = extrinsic_detail.as_extrinsic::<Transact>()? {
                 if let TransactionV2::Legacy(tx) = transaction {
-                    if let TransactionAction::Call(_) = tx.action {
+                    let benchmarked = match tx.action {
+                        TransactionAction::Call(_) => !deployments,
+                        TransactionAction::Create => deployments,
+                    };
+                    if benchmarked {
                         let events = extrinsic_detail.events().await?;
                         for event in events.iter() {
                             let event = event?;
                             if let Some(Executed {
-                                transaction_hash, ..
+                                from,
+                                to,
+                                transaction_hash,
+                                exit_reason,
+                                extra_data,
                             }) = event.as_event::<Executed>()?
                             {
-                                tx_hashes.push(transaction_hash);
+                                let outcome = match exit_reason {
+                                    ExitReason::Succeed(_) => CallOutcome::Success,
+                                    ExitReason::Revert(_) => {
+                                        // the message of a Solidity `Error(string)` is recorded
+                                        // with the revert, other outputs only by replaying it
+                                        let mut reason = recorded_revert_reason(&extra_data);
+                                        if reason.is_none()
+                                            && !deployments
+                                            && revert_reasons < MAX_REVERT_REASONS
+                                        {
+                                            revert_reasons += 1;
+                                            let parent = match eth_parent_hash {
+                                                Some(parent) => parent,
+                                                None => *eth_parent_hash.insert(
+                                                    self.eth_parent_hash(block_hash).await?,
+                                                ),
+                                            };
+                                            reason = self
+                                                .replay_reverted_call(
+                                                    Address::from_slice(from.as_ref()),
+                                                    Address::from_slice(to.as_ref()),
+                                                    U256(tx.value.0),
+                                                    &tx.input,
+                                                    parent,
+                                                )
+                                                .await
+                                                .map(RevertReason::Replayed);
+                                        }
+                                        CallOutcome::Reverted(reason)
+                                    }
+                                    exit_reason => {
+                                        CallOutcome::DispatchError(format!("{exit_reason:?}"))
+                                    }
+                                };
+                                // EVM contracts don't hold storage deposits
+                                calls.push(IncludedCall {
+                                    hash: transaction_hash,
+                                    storage_deposit: Default::default(),
+                                    outcome,
+                                });
                             }
                         }
                     }
@@ -245,71 +483,338 @@ impl MoonbeamRunner {
             .fetch(&storage_timestamp_storage_addr)
             .await?
             .unwrap();
-        Ok((time_stamp, tx_hashes))
+        Ok(BlockDetails { time_stamp, calls })
     }
 
-    /// Call each contract instance `call_count` times. Wait for all txs to be included in a block
-    /// before returning.
+    /// The hash of the parent of the Ethereum block built in the block with the given hash. It is
+    /// read from the state of that block, so it is the parent on the same fork.
+    async fn eth_parent_hash(&self, block_hash: sp_core::H256) -> color_eyre::Result<H256> {
+        let current_block_addr = api::storage().ethereum().current_block();
+        let current_block = self
+            .api
+            .client()
+            .storage()
+            .at(block_hash)
+            .fetch(&current_block_addr)
+            .await?
+            .ok_or_else(|| eyre::eyre!("No Ethereum block in block {block_hash:?}"))?;
+        Ok(H256::from_slice(current_block.header.parent_hash.as_ref()))
+    }
+
+    /// The reason a call reverted with, by replaying it with `eth_call` on the state of the parent
+    /// of its block, given by its Ethereum `parent` hash, `None` if the replay didn't revert as
+    /// well.
+    async fn replay_reverted_call(
+        &self,
+        from: Address,
+        contract: Address,
+        value: U256,
+        data: &[u8],
+        parent: H256,
+    ) -> Option<String> {
+        match self.api.call_at(from, contract, value, data, parent).await {
+            Ok(_) => {
+                tracing::debug!("Replayed reverted call succeeded at block {parent:?}");
+                None
+            }
+            Err(web3::Error::Rpc(error)) => {
+                // the output of the reverted call is returned as the hex data of the error
+                let output = error
+                    .data
+                    .as_ref()
+                    .and_then(|data| data.as_str())
+                    .and_then(|data| from_hex(data).ok());
+                Some(match output {
+                    Some(output) => outcome::revert_reason(&output),
+                    None => error.message,
+                })
+            }
+            Err(err) => {
+                tracing::debug!("Error replaying reverted call: {err:?}");
+                None
+            }
+        }
+    }
+
+    /// Sign all calls of the `schedule` up front, for `--pre-sign`. The signing is spread across
+    /// the CPU cores.
+    fn pre_sign_calls(
+        &self,
+        schedule: Schedule,
+        signers: &mut [(&SecretKey, U256)],
+        gas_price: U256,
+    ) -> Vec<(&PreparedContract, Bytes)> {
+        eprintln!("Pre-signing calls...");
+        let mut contracts = Vec::new();
+        let mut transactions = Vec::new();
+        for (call, (contract, instance)) in schedule.enumerate() {
+            let contract = &self.contracts[contract];
+            let contract_call = &contract.calls[instance];
+            let (signer, nonce) = next_nonce(signers, call);
+            let tx = MoonbeamApi::call_transaction(
+                contract_call.contract,
+                &contract_call.data,
+                nonce,
+                contract.value.for_call(call).into(),
+                contract_call.gas_limit,
+                gas_price,
+            );
+            contracts.push(contract);
+            transactions.push((tx, signer));
+        }
+        let chain_id = self.api.chain_id();
+        let signed_calls = crate::presign::sign_in_parallel(transactions, |(tx, signer)| {
+            tx.sign(signer, chain_id).raw_transaction
+        });
+        eprintln!("Pre-signed {} calls", signed_calls.len());
+        contracts.into_iter().zip(signed_calls).collect()
+    }
+
+    /// The label, number of instances and call count of each prepared contract, to schedule the
+    /// calls with.
+    pub fn workloads(&self) -> impl Iterator<Item = (&str, usize, Option<u32>)> {
+        self.contracts.iter().map(|contract| {
+            (
+                contract.label.as_str(),
+                contract.calls.len(),
+                contract.call_count,
+            )
+        })
+    }
+
+    /// The `--signers` the calls are spread across, or the signer if there are none, with their
+    /// next nonces.
+    async fn signer_nonces(&self) -> color_eyre::Result<Vec<(&SecretKey, U256)>> {
+        let mut signers = Vec::new();
+        if self.call_signers.is_empty() {
+            signers.push((&self.signer, self.api.fetch_nonce(self.address).await?));
+        } else {
+            for signer in &self.call_signers {
+                let address = Key::address(&SecretKeyRef::from(signer));
+                signers.push((signer, self.api.fetch_nonce(address).await?));
+            }
+        }
+        Ok(signers)
+    }
+
+    /// Call the contract instances in the order of the `schedule`, at the given `rate` or as fast
+    /// as possible, with up to `max_in_flight` calls being submitted at once. With `pre_sign`, all calls are signed before any is submitted.
+    /// The returned stream yields the blocks while the calls are submitted, and completes once
+    /// all of them are included in a block.
     pub async fn run(
         &mut self,
-        call_count: u32,
+        finalized_blocks: FinalizedBlocks,
+        schedule: Schedule,
+        rate: Option<Rate>,
+        max_in_flight: NonZeroUsize,
+        pre_sign: bool,
     ) -> color_eyre::Result<impl TryStream<Ok = BlockInfo, Error = color_eyre::Report> + '_> {
+        if self.contracts.is_empty() {
+            return Err(eyre::eyre!("No prepared contracts for benchmarking."));
+        }
+        let mut signers = self.signer_nonces().await?;
+        let gas_price = self.api.get_gas_price().await.note("Error getting gas")?;
+
+        let this = &*self;
+        let signed_calls = if pre_sign {
+            let calls = this.pre_sign_calls(schedule, &mut signers, gas_price);
+            crate::rate::paced(calls.into_iter(), rate).left_stream()
+        } else {
+            let chain_id = this.api.chain_id();
+            crate::rate::paced(schedule.enumerate(), rate)
+                .map(move |(call, (contract, instance))| {
+                    let contract = &this.contracts[contract];
+                    let contract_call = &contract.calls[instance];
+                    // the nonces are assigned in the order of the calls, even though up to
+                    // `max_in_flight` of them are submitted concurrently
+                    let (signer, nonce) = next_nonce(&mut signers, call);
+                    tracing::debug!(
+                        "Calling {}, address {}, gas_limit {}",
+                        contract_call.name,
+                        contract_call.contract,
+                        contract_call.gas_limit
+                    );
+                    let tx = MoonbeamApi::call_transaction(
+                        contract_call.contract,
+                        &contract_call.data,
+                        nonce,
+                        contract.value.for_call(call).into(),
+                        contract_call.gas_limit,
+                        gas_price,
+                    );
+                    (contract, tx.sign(signer, chain_id).raw_transaction)
+                })
+                .right_stream()
+        };
+
         let block_stats = blockstats::subscribe_stats(&self.url).await?;
+        let pending = PendingCalls::default();
+        let submitted_calls = pending.clone();
+        let submit_calls = async move {
+            let mut submitted_count = 0;
+            signed_calls
+                .map(|(contract, tx)| async move {
+                    let submitted = Instant::now();
+                    let tx_hash = this.api.send_raw_transaction(tx).await?;
+                    Ok::<_, color_eyre::Report>((contract, tx_hash, submitted))
+                })
+                .buffer_unordered(max_in_flight.get())
+                .try_for_each(|(contract, tx_hash, submitted)| {
+                    submitted_calls.insert(
+                        sp_core::H256::from_slice(tx_hash.as_ref()),
+                        SubmittedCall {
+                            contract: contract.label.clone(),
+                            submitted,
+                        },
+                    );
+                    submitted_count += 1;
+                    crate::metrics::tx_submitted();
+                    future::ready(Ok(()))
+                })
+                .await?;
+            submitted_calls.finish();
+            eprintln!("Submitted {submitted_count} total contract calls");
+            Ok::<_, color_eyre::Report>(())
+        };
 
-        let mut tx_hashes = Vec::new();
-        let max_instance_count = self
-            .calls
-            .iter()
-            .map(|(_, calls)| calls.len())
-            .max()
-            .ok_or_else(|| eyre::eyre!("No prepared contracts for benchmarking."))?;
-        let mut nonce = self.api.fetch_nonce(self.address).await?;
-        let gas_price = self.api.get_gas_price().await.note("Error getting gas")?;
+        let wait_for_txs = crate::collect_block_stats(
+            block_stats,
+            pending,
+            this.finality,
+            finalized_blocks,
+            move |hash| this.get_block_details(hash, false),
+        );
 
-        for _ in 0..call_count {
-            for i in 0..max_instance_count {
-                for (_name, contract_calls) in &self.calls {
-                    if let Some(contract_call) = contract_calls.get(i as usize) {
-                        tracing::debug!(
-                            "Calling {}, address {}, gas_limit {}",
-                            contract_call.name,
-                            contract_call.contract,
-                            contract_call.gas_limit
-                        );
-                        let tx_hash = self
-                            .api
-                            .call(
-                                contract_call.contract,
-                                &contract_call.data,
-                                &self.signer,
-                                nonce,
-                                contract_call.gas_limit,
-                                gas_price,
-                            )
-                            .await?;
-                        nonce += 1.into();
-                        tx_hashes.push(tx_hash)
-                    }
-                }
-            }
+        Ok(crate::stats::submit_while_collecting(
+            submit_calls,
+            wait_for_txs,
+        ))
+    }
+
+    /// Deploy the instances of the contracts prepared with `--deployments`, at the given `rate`
+    /// or as fast as possible, with up to `max_in_flight` deployments being submitted at once.
+    /// They are spread across the `--signers` like calls. The returned stream yields the blocks
+    /// while the deployments are submitted, and completes once all of them are included in a
+    /// block.
+    pub async fn run_deployments(
+        &mut self,
+        finalized_blocks: FinalizedBlocks,
+        rate: Option<Rate>,
+        max_in_flight: NonZeroUsize,
+    ) -> color_eyre::Result<impl TryStream<Ok = BlockInfo, Error = color_eyre::Report> + '_> {
+        if self.deployments.is_empty() {
+            return Err(eyre::eyre!("No prepared contracts for benchmarking."));
         }
+        let mut signers = self.signer_nonces().await?;
+        let gas_price = self.api.get_gas_price().await.note("Error getting gas")?;
 
-        println!("Submitted {} total contract calls", tx_hashes.len());
+        let this = &*self;
+        let chain_id = this.api.chain_id();
+        let instances = this.deployments.iter().flat_map(|deployment| {
+            (0..deployment.instance_count).map(move |instance| (deployment, instance))
+        });
+        let signed_deployments = crate::rate::paced(instances.enumerate(), rate).map(
+            move |(index, (deployment, instance))| {
+                // the nonces are assigned in the order of the deployments, even though up to
+                // `max_in_flight` of them are submitted concurrently
+                let (signer, nonce) = next_nonce(&mut signers, index);
+                let tx = MoonbeamApi::deploy_transaction(
+                    &deployment.data,
+                    nonce,
+                    deployment.value.for_call(instance as usize).into(),
+                    deployment.gas_limit,
+                    gas_price,
+                );
+                (deployment, tx.sign(signer, chain_id).raw_transaction)
+            },
+        );
 
-        let remaining_hashes: std::collections::HashSet<sp_core::H256> = tx_hashes
-            .iter()
-            .map(|hash| sp_core::H256::from_slice(hash.as_ref()))
-            .collect();
+        let block_stats = blockstats::subscribe_stats(&self.url).await?;
+        let pending = PendingCalls::default();
+        let submitted_calls = pending.clone();
+        let submit_calls = async move {
+            let mut submitted_count = 0;
+            signed_deployments
+                .map(|(deployment, tx)| async move {
+                    let submitted = Instant::now();
+                    let tx_hash = this.api.send_raw_transaction(tx).await?;
+                    Ok::<_, color_eyre::Report>((deployment, tx_hash, submitted))
+                })
+                .buffer_unordered(max_in_flight.get())
+                .try_for_each(|(deployment, tx_hash, submitted)| {
+                    submitted_calls.insert(
+                        sp_core::H256::from_slice(tx_hash.as_ref()),
+                        SubmittedCall {
+                            contract: deployment.label.clone(),
+                            submitted,
+                        },
+                    );
+                    submitted_count += 1;
+                    crate::metrics::tx_submitted();
+                    future::ready(Ok(()))
+                })
+                .await?;
+            submitted_calls.finish();
+            eprintln!("Submitted {submitted_count} total deployments");
+            Ok::<_, color_eyre::Report>(())
+        };
 
-        let wait_for_txs = crate::collect_block_stats(block_stats, remaining_hashes, |hash| {
-            let client = self.api.client.clone();
-            Self::get_block_details(client, hash)
-        });
+        let wait_for_txs = crate::collect_block_stats(
+            block_stats,
+            pending,
+            this.finality,
+            finalized_blocks,
+            move |hash| this.get_block_details(hash, true),
+        );
 
-        Ok(wait_for_txs)
+        Ok(crate::stats::submit_while_collecting(
+            submit_calls,
+            wait_for_txs,
+        ))
     }
 }
 
+/// The next signer to make the call with the given index, and its nonce for that call.
+fn next_nonce<'a>(signers: &mut [(&'a SecretKey, U256)], call: usize) -> (&'a SecretKey, U256) {
+    let signer_count = signers.len();
+    let (signer, nonce) = &mut signers[call % signer_count];
+    let call_nonce = *nonce;
+    *nonce += 1.into();
+    (*signer, call_nonce)
+}
+
+/// The reason of a revert recorded in the `Ethereum::Executed` event: the message of a Solidity
+/// `Error(string)`, which the runtime may truncate.
+fn recorded_revert_reason(extra_data: &[u8]) -> Option<RevertReason> {
+    (!extra_data.is_empty()).then(|| {
+        RevertReason::Recorded(format!("Error({:?})", String::from_utf8_lossy(extra_data)))
+    })
+}
+
+/// The calls to the instances of a contract.
+struct PreparedContract {
+    /// The name the calls are reported under.
+    label: String,
+    /// The number of calls to make to each instance, `None` to call them until the duration of
+    /// the run has elapsed.
+    call_count: Option<u32>,
+    calls: Vec<RunnerCall>,
+    /// The value transferred with the calls.
+    value: CallValue,
+}
+
+/// The deployment of the instances of a contract.
+struct PreparedDeployment {
+    /// The name the deployments are reported under.
+    label: String,
+    /// The value transferred with each deployment.
+    value: CallValue,
+    /// The bytecode and encoded constructor arguments.
+    data: Vec<u8>,
+    gas_limit: U256,
+    instance_count: u32,
+}
+
 struct RunnerCall {
     name: String,
     contract: Address,
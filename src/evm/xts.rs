@@ -4,7 +4,7 @@ use subxt::{OnlineClient, PolkadotConfig as DefaultConfig};
 use web3::{
     signing::Key,
     transports::ws,
-    types::{Address, CallRequest, H256, U256},
+    types::{Address, BlockId, Bytes, CallRequest, TransactionReceipt, H256, U256},
     Web3,
 };
 
@@ -17,6 +17,9 @@ use web3::{
 )]
 pub mod api {}
 
+/// The gas of a plain value transfer.
+const TRANSFER_GAS: u32 = 21_000;
+
 pub struct MoonbeamApi {
     web3: Web3<ws::WebSocket>,
     pub client: OnlineClient<DefaultConfig>,
@@ -65,6 +68,7 @@ impl MoonbeamApi {
         &self,
         from: Address,
         contract: Option<Address>,
+        value: U256,
         data: &[u8],
     ) -> color_eyre::Result<U256> {
         let call_request = CallRequest {
@@ -72,7 +76,7 @@ impl MoonbeamApi {
             to: contract,
             gas: None,
             gas_price: None,
-            value: None,
+            value: Some(value),
             data: Some(data.into()),
             transaction_type: None,
             access_list: None,
@@ -86,55 +90,134 @@ impl MoonbeamApi {
             .map_err(Into::into)
     }
 
+    /// Call a contract with `eth_call` on the state of the Ethereum block with the given hash,
+    /// returning its output.
+    pub async fn call_at(
+        &self,
+        from: Address,
+        contract: Address,
+        value: U256,
+        data: &[u8],
+        block_hash: H256,
+    ) -> web3::Result<Bytes> {
+        let call_request = CallRequest {
+            from: Some(from),
+            to: Some(contract),
+            gas: None,
+            gas_price: None,
+            value: Some(value),
+            data: Some(data.into()),
+            transaction_type: None,
+            access_list: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+        };
+        self.web3
+            .eth()
+            .call(call_request, Some(BlockId::Hash(block_hash)))
+            .await
+    }
+
     pub async fn deploy(
         &self,
         data: &[u8],
         signer: impl Key,
         nonce: U256,
+        value: U256,
         gas: U256,
         gas_price: U256,
     ) -> color_eyre::Result<H256> {
-        self.sign_and_submit_tx(data, signer, nonce, None, gas, gas_price)
-            .await
+        let tx = Self::deploy_transaction(data, nonce, value, gas, gas_price);
+        self.submit_tx(tx, signer).await
     }
 
-    pub async fn call(
-        &self,
+    /// The transaction to deploy a contract, to be signed with the [`Self::chain_id`] and
+    /// submitted with [`Self::send_raw_transaction`].
+    pub fn deploy_transaction(
+        data: &[u8],
+        nonce: U256,
+        value: U256,
+        gas: U256,
+        gas_price: U256,
+    ) -> Transaction {
+        Transaction {
+            nonce,
+            to: None,
+            gas,
+            gas_price,
+            value,
+            data: data.into(),
+            transaction_type: None,
+            access_list: Default::default(),
+            max_priority_fee_per_gas: gas_price,
+        }
+    }
+
+    /// The transaction to call a contract, to be signed with the [`Self::chain_id`] and submitted
+    /// with [`Self::send_raw_transaction`].
+    pub fn call_transaction(
         contract: Address,
         data: &[u8],
-        signer: impl Key,
         nonce: U256,
+        value: U256,
         gas: U256,
         gas_price: U256,
-    ) -> color_eyre::Result<H256> {
-        self.sign_and_submit_tx(data, signer, nonce, Some(contract), gas, gas_price)
-            .await
+    ) -> Transaction {
+        Transaction {
+            nonce,
+            to: Some(contract),
+            gas,
+            gas_price,
+            value,
+            data: data.into(),
+            transaction_type: None,
+            access_list: Default::default(),
+            max_priority_fee_per_gas: gas_price,
+        }
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id.as_u64()
     }
 
-    pub async fn sign_and_submit_tx(
+    /// Transfer `value` to the account `to`.
+    pub async fn transfer(
         &self,
-        data: &[u8],
+        to: Address,
+        value: U256,
         signer: impl Key,
         nonce: U256,
-        to: Option<Address>,
-        gas: U256,
         gas_price: U256,
     ) -> color_eyre::Result<H256> {
         let tx = Transaction {
             nonce,
-            to,
-            gas,
+            to: Some(to),
+            gas: TRANSFER_GAS.into(),
             gas_price,
-            value: 0u32.into(),
-            data: data.into(),
+            value,
+            data: Vec::new(),
             transaction_type: None,
             access_list: Default::default(),
             max_priority_fee_per_gas: gas_price,
         };
+        self.submit_tx(tx, signer).await
+    }
 
+    pub async fn transaction_receipt(
+        &self,
+        tx_hash: H256,
+    ) -> color_eyre::Result<Option<TransactionReceipt>> {
+        self.web3
+            .eth()
+            .transaction_receipt(tx_hash)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn submit_tx(&self, tx: Transaction, signer: impl Key) -> color_eyre::Result<H256> {
         let signed_tx = tx.sign(signer, self.chain_id.as_u64());
 
-        tracing::debug!("data: {}", to_hex(data, false));
+        tracing::debug!("data: {}", to_hex(&tx.data, false));
         tracing::debug!(
             "signed_tx.raw_transaction: {}",
             to_hex(&signed_tx.raw_transaction.0, false)
@@ -144,10 +227,14 @@ impl MoonbeamApi {
             "signed_tx.transaction_hash: {:?}",
             signed_tx.transaction_hash
         );
+        self.send_raw_transaction(signed_tx.raw_transaction).await
+    }
+
+    pub async fn send_raw_transaction(&self, raw_transaction: Bytes) -> color_eyre::Result<H256> {
         let hash = self
             .web3
             .eth()
-            .send_raw_transaction(signed_tx.raw_transaction)
+            .send_raw_transaction(raw_transaction)
             .await?;
 
         Ok(hash)
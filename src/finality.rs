@@ -0,0 +1,99 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use subxt::{OnlineClient, PolkadotConfig as DefaultConfig};
+use tokio::sync::watch;
+
+/// Follows the finalized blocks of the chain, recording when each of them was seen finalized.
+#[derive(Clone)]
+pub struct FinalizedBlocks {
+    blocks: Arc<Mutex<BTreeMap<u64, (sp_core::H256, Instant)>>>,
+    /// Number of the finalized block at the time of subscribing.
+    initial: u64,
+    best_finalized: watch::Receiver<u64>,
+}
+
+impl FinalizedBlocks {
+    pub async fn subscribe(url: &str) -> color_eyre::Result<Self> {
+        let client = OnlineClient::<DefaultConfig>::from_url(url).await?;
+        let mut block_sub = client.blocks().subscribe_finalized().await?;
+
+        // the subscription starts with the current finalized block
+        let initial: u64 = match block_sub.next().await {
+            Some(block) => block?.number().into(),
+            None => 0,
+        };
+        let blocks = Arc::new(Mutex::new(BTreeMap::new()));
+        let (sender, best_finalized) = watch::channel(initial);
+
+        let finalized_blocks = blocks.clone();
+        tokio::spawn(async move {
+            while let Some(block) = block_sub.next().await {
+                match block {
+                    Ok(block) => {
+                        let number: u64 = block.number().into();
+                        finalized_blocks
+                            .lock()
+                            .unwrap()
+                            .insert(number, (block.hash(), Instant::now()));
+                        let _ = sender.send(number);
+                    }
+                    Err(err) => {
+                        tracing::warn!("Error following finalized blocks: {err}");
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            blocks,
+            initial,
+            best_finalized,
+        })
+    }
+
+    /// Whether any block was finalized since subscribing. Chains sealing blocks without finality
+    /// (e.g. dev nodes with instant seal) never finalize the benchmark blocks.
+    pub fn is_progressing(&self) -> bool {
+        *self.best_finalized.borrow() > self.initial
+    }
+
+    /// Wait until the block with the given number is finalized. Returns the hash of the finalized
+    /// block at that height and the time it was seen finalized, or `None` if the subscription
+    /// ended before.
+    pub async fn wait_for(&self, number: u64) -> Option<(sp_core::H256, Instant)> {
+        let mut best_finalized = self.best_finalized.clone();
+        while *best_finalized.borrow_and_update() < number {
+            best_finalized.changed().await.ok()?;
+        }
+        self.get(number)
+    }
+
+    /// The hash of the finalized block at the given height and the time it was seen finalized,
+    /// if it is finalized already.
+    pub fn get(&self, number: u64) -> Option<(sp_core::H256, Instant)> {
+        self.blocks.lock().unwrap().get(&number).copied()
+    }
+
+    /// Forget the blocks below the given height, which are no longer looked up.
+    pub fn prune_before(&self, number: u64) {
+        let mut blocks = self.blocks.lock().unwrap();
+        *blocks = blocks.split_off(&number);
+    }
+}
+
+#[cfg(test)]
+impl FinalizedBlocks {
+    /// No finalized blocks, for collecting blocks with [`crate::Finality::Best`] without a node.
+    pub fn none() -> Self {
+        let (_, best_finalized) = watch::channel(0);
+        Self {
+            blocks: Default::default(),
+            initial: 0,
+            best_finalized,
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use crate::{
+    rate::Rate,
+    report::Environment,
+    stats::{self, BlockInfo, Summary},
+    stps::StpsCalculator,
+    Cli, OutputFormat,
+};
+use color_eyre::{eyre, Section as _};
+use futures::TryStream;
+use serde::Serialize;
+use std::{fmt, io::Write, time::Duration};
+
+/// The share of the rate of a step which has to be submitted, and then included, to keep up.
+const MIN_THROUGHPUT_RATIO: f64 = 0.9;
+/// The block utilisation from which the blocks are full: normal extrinsics may only use 75% of a
+/// block.
+const SATURATED_UTILISATION: f64 = 0.7;
+
+/// Why the calls of a step could not keep up with its rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Saturation {
+    /// The client could not submit the calls at the rate.
+    ClientLimited,
+    /// The chain included fewer calls per second than were submitted, so the backlog and the
+    /// latency keep growing.
+    FallingBehind,
+    /// The blocks are full.
+    FullBlocks,
+}
+
+impl Saturation {
+    fn of(rate: Rate, summary: &Summary) -> Option<Self> {
+        let submission_rate = summary.submission_rate.unwrap_or(rate.per_second());
+        if submission_rate < rate.per_second() * MIN_THROUGHPUT_RATIO {
+            Some(Self::ClientLimited)
+        } else if summary
+            .stps
+            .is_some_and(|stps| stps < submission_rate * MIN_THROUGHPUT_RATIO)
+        {
+            Some(Self::FallingBehind)
+        } else if summary.block_utilisation.is_some_and(|utilisation| {
+            utilisation
+                .ref_time
+                .max(utilisation.proof_size)
+                .max(utilisation.pov)
+                >= SATURATED_UTILISATION
+        }) {
+            Some(Self::FullBlocks)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Saturation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ClientLimited => write!(f, "client limited"),
+            Self::FallingBehind => write!(f, "falling behind"),
+            Self::FullBlocks => write!(f, "full blocks"),
+        }
+    }
+}
+
+/// A load level of `--find-max`.
+#[derive(Debug, Serialize)]
+pub struct Step {
+    pub rate: Rate,
+    /// `None` if the chain sustained the rate.
+    pub saturation: Option<Saturation>,
+    pub summary: Summary,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rate {}:", self.rate)?;
+        if let Some(submission_rate) = self.summary.submission_rate {
+            write!(f, " Submitted={submission_rate:.2}/s")?;
+        }
+        match self.summary.stps {
+            Some(stps) => write!(f, " sTPS={stps:.2}")?,
+            None => write!(f, " sTPS=n/a")?,
+        }
+        if let Some(latency) = &self.summary.in_block_latency {
+            write!(f, " Latency p90={:.0}ms", latency.p90_ms)?;
+        }
+        write!(f, " Max Backlog={}", self.summary.max_backlog)?;
+        if let Some(utilisation) = &self.summary.block_utilisation {
+            write!(f, " {utilisation}")?;
+        }
+        match self.saturation {
+            Some(saturation) => write!(f, " -> saturated ({saturation})"),
+            None => write!(f, " -> sustained"),
+        }
+    }
+}
+
+/// Searches the maximum throughput the chain sustains with `--find-max`: the contracts are
+/// called at increasing rates until the chain (or the client) can't keep up.
+pub struct FindMax {
+    /// The rates of the steps, from `--rate` in steps of `--rate-step`.
+    rates: Vec<Rate>,
+    calculator: StpsCalculator,
+    steps: Vec<Step>,
+    /// Whether the human readable output is printed, see [`crate::print_block_info`].
+    print_text: bool,
+}
+
+impl FindMax {
+    pub fn new(cli: &Cli) -> color_eyre::Result<Self> {
+        if cli.output_format.appends() {
+            return Err(eyre::eyre!(
+                "--find-max reports its results as text or json only"
+            ))
+            .suggestion("Use --output-format json for a machine readable report");
+        }
+        let start = cli
+            .rate
+            .ok_or_else(|| eyre::eyre!("Missing the rate of the first step"))
+            .suggestion("Provide --rate")?;
+        let step = cli.rate_step.unwrap_or(start);
+        let rates = (0..cli.max_steps)
+            .map(|step_index| {
+                Rate::new(start.per_second() + step.per_second() * f64::from(step_index))
+            })
+            .collect();
+        Ok(Self {
+            rates,
+            calculator: StpsCalculator::new(
+                cli.stps_strategy,
+                Duration::from_millis(cli.slot_time),
+            ),
+            steps: Vec::new(),
+            print_text: cli.prints_text(),
+        })
+    }
+
+    /// The rate of the next step, `None` once a step saturated the chain or all steps are done.
+    pub fn next_rate(&self) -> Option<Rate> {
+        if self
+            .steps
+            .last()
+            .is_some_and(|step| step.saturation.is_some())
+        {
+            return None;
+        }
+        self.rates.get(self.steps.len()).copied()
+    }
+
+    /// Summarise the blocks of the step at the given rate.
+    pub async fn record(
+        &mut self,
+        rate: Rate,
+        block_info: impl TryStream<Ok = BlockInfo, Error = color_eyre::Report>,
+    ) -> color_eyre::Result<()> {
+        let blocks = stats::collect_blocks(block_info, false).await?;
+        let summary = Summary::new(&blocks, &self.calculator);
+        let step = Step {
+            rate,
+            saturation: Saturation::of(rate, &summary),
+            summary,
+        };
+        if self.print_text {
+            println!("{step}");
+        }
+        self.steps.push(step);
+        Ok(())
+    }
+
+    /// The step with the highest sTPS the chain sustained.
+    fn max_sustained(&self) -> Option<&Step> {
+        self.steps
+            .iter()
+            .filter(|step| step.saturation.is_none())
+            .filter(|step| step.summary.stps.is_some())
+            .max_by(|a, b| {
+                a.summary
+                    .stps
+                    .partial_cmp(&b.summary.stps)
+                    .expect("sTPS is a number")
+            })
+    }
+
+    /// Print the results, and write the report in the format selected with `--output-format`.
+    pub fn write_results(&self, cli: &Cli, environment: &Environment) -> color_eyre::Result<()> {
+        if self.print_text {
+            self.write_text(&mut std::io::stdout())?;
+        }
+        if cli.output_format == OutputFormat::Json {
+            let report = FindMaxReport {
+                timestamp: cli.timestamp()?,
+                parameters: cli,
+                environment,
+                steps: &self.steps,
+                max_sustained_rate: self.max_sustained().map(|step| step.rate),
+                stps: self.max_sustained().and_then(|step| step.summary.stps),
+            };
+            let mut writer: Box<dyn Write> = match &cli.output_file {
+                Some(path) => Box::new(std::fs::File::create(path)?),
+                None => Box::new(std::io::stdout().lock()),
+            };
+            serde_json::to_writer_pretty(&mut writer, &report)?;
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    fn write_text(&self, w: &mut impl Write) -> std::io::Result<()> {
+        writeln!(w, "\nSteps:")?;
+        for step in &self.steps {
+            writeln!(w, "  {step}")?;
+        }
+        if let Some(step) = self.steps.iter().find(|step| step.saturation.is_some()) {
+            writeln!(w, "Saturated At: {}", step.rate)?;
+        } else {
+            writeln!(w, "Saturated At: n/a, the chain sustained all rates")?;
+        }
+        match self.max_sustained() {
+            Some(step) => {
+                let stps = step.summary.stps.expect("sustained steps have an sTPS");
+                writeln!(w, "Max Sustained Rate: {}", step.rate)?;
+                writeln!(w, "sTPS - Standard Transaction Per Second")?;
+                writeln!(w, "sTPS: {stps:.2}")?;
+            }
+            None => writeln!(
+                w,
+                "sTPS - Error - no rate was sustained, consider a lower --rate"
+            )?,
+        }
+        Ok(())
+    }
+}
+
+/// The results of `--find-max` in a machine readable form.
+#[derive(Serialize)]
+struct FindMaxReport<'a> {
+    /// Unix timestamp (in seconds) of the end of the search.
+    timestamp: u64,
+    parameters: &'a Cli,
+    environment: &'a Environment,
+    steps: &'a [Step],
+    max_sustained_rate: Option<Rate>,
+    /// The sTPS at the maximum sustained rate.
+    stps: Option<f64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{stats::Utilisation, StpsStrategy};
+
+    fn summary(submission_rate: f64, stps: f64, utilisation: f64) -> Summary {
+        Summary {
+            total_blocks: 10,
+            total_extrinsics: 1000,
+            block_build_time: 6.0,
+            stps_strategy: StpsStrategy::Timestamp,
+            stps: Some(stps),
+            block_utilisation: Some(Utilisation {
+                ref_time: utilisation,
+                proof_size: 0.1,
+                pov: 0.1,
+            }),
+            contracts: Default::default(),
+            in_block_latency: None,
+            finalized_latency: None,
+            submission_rate: Some(submission_rate),
+            inclusion_rate: Some(stps),
+            max_backlog: 0,
+            storage_deposit: Default::default(),
+            failed_calls: Default::default(),
+        }
+    }
+
+    #[test]
+    fn detects_saturation() {
+        let rate = Rate::new(100.0);
+        assert_eq!(Saturation::of(rate, &summary(99.0, 98.0, 0.5)), None);
+        assert_eq!(
+            Saturation::of(rate, &summary(80.0, 80.0, 0.5)),
+            Some(Saturation::ClientLimited)
+        );
+        assert_eq!(
+            Saturation::of(rate, &summary(100.0, 70.0, 0.5)),
+            Some(Saturation::FallingBehind)
+        );
+        assert_eq!(
+            Saturation::of(rate, &summary(100.0, 100.0, 0.74)),
+            Some(Saturation::FullBlocks)
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use serde::Serialize;
+use std::{fmt, str::FromStr};
+
+/// The fixed gas limit of the calls to Wasm contracts with `--gas-limit`, e.g.
+/// `5000000000,300000` for its `ref_time` and `proof_size`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasLimit {
+    pub ref_time: u64,
+    pub proof_size: u64,
+}
+
+impl FromStr for GasLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ref_time, proof_size) = s
+            .split_once(',')
+            .ok_or_else(|| format!("Invalid gas limit {s}, expected ref_time,proof_size"))?;
+        let parse = |weight: &str| {
+            weight
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| format!("Invalid gas limit {s}, expected e.g. 5000000000,300000"))
+        };
+        Ok(Self {
+            ref_time: parse(ref_time)?,
+            proof_size: parse(proof_size)?,
+        })
+    }
+}
+
+impl fmt::Display for GasLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.ref_time, self.proof_size)
+    }
+}
+
+impl Serialize for GasLimit {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_gas_limit() {
+        assert_eq!(
+            GasLimit::from_str("5000000000, 300000").unwrap(),
+            GasLimit {
+                ref_time: 5_000_000_000,
+                proof_size: 300_000
+            }
+        );
+        assert!(GasLimit::from_str("5000000000").is_err());
+        assert!(GasLimit::from_str("5000000000,-1").is_err());
+    }
+}
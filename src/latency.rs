@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Distribution of the latencies of the benchmark calls.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Latency {
+    /// The number of calls the latency was measured for.
+    pub calls: usize,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Latency {
+    /// Percentiles of the given latencies, `None` if there are none.
+    pub fn new(mut latencies: Vec<Duration>) -> Option<Self> {
+        if latencies.is_empty() {
+            return None;
+        }
+        latencies.sort();
+        // nearest-rank method
+        let percentile = |p: usize| {
+            let rank = (latencies.len() * p + 99) / 100;
+            latencies[rank.max(1) - 1].as_secs_f64() * 1000.0
+        };
+        Some(Self {
+            calls: latencies.len(),
+            p50_ms: percentile(50),
+            p90_ms: percentile(90),
+            p99_ms: percentile(99),
+            max_ms: percentile(100),
+        })
+    }
+}
+
+impl std::fmt::Display for Latency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "p50={:.0}ms p90={:.0}ms p99={:.0}ms max={:.0}ms",
+            self.p50_ms, self.p90_ms, self.p99_ms, self.max_ms
+        )
+    }
+}
@@ -1,18 +1,44 @@
+mod compare;
 mod evm;
+mod finality;
+mod find_max;
+mod gas;
 #[cfg(test)]
 #[cfg(feature = "integration-tests")]
 mod integration_tests;
+mod latency;
+mod metrics;
+mod outcome;
+mod presign;
+mod profile;
+mod rate;
+mod report;
+mod scenario;
+mod schedule;
 mod stats;
+mod stps;
+mod value;
 mod wasm;
 
-use std::fmt::Display;
+use std::{
+    fmt::Display,
+    net::SocketAddr,
+    num::NonZeroUsize,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 // export for use by contract! macro
 use clap::Parser;
+use gas::GasLimit;
+use rate::Rate;
+use schedule::Mix;
+use serde::Serialize;
 pub use stats::{collect_block_stats, print_block_info, BlockInfo};
+use value::CallValue;
 pub use wasm::{InkConstructor, InkMessage};
 
-#[derive(Debug, Parser)]
+#[derive(Debug, Parser, Serialize)]
 #[clap(version)]
 pub struct Cli {
     /// the url of the substrate node for submitting the extrinsics.
@@ -22,24 +48,207 @@ pub struct Cli {
     #[clap(arg_enum)]
     chain: TargetPlatform,
     /// the list of contracts to benchmark with.
-    #[clap(arg_enum)]
+    #[clap(
+        arg_enum,
+        required_unless_present_any = &["scenario", "contract-bundle", "artifact", "mix"]
+    )]
     contracts: Vec<Contract>,
     /// the number of each contract to instantiate.
-    #[clap(long, short)]
-    instance_count: u32,
-    /// the number of calls to make to each contract.
-    #[clap(long, short)]
-    call_count: u32,
+    #[clap(long, short, required_unless_present = "scenario")]
+    instance_count: Option<u32>,
+    /// the number of calls to make to each contract, or in total with --mix.
+    #[clap(long, short, required_unless_present_any = &["scenario", "duration", "deployments"])]
+    call_count: Option<u32>,
+    /// keep calling the contracts for the given time, e.g. 90s, 5m or 1h, instead of (or at most
+    /// until) making --call-count calls, then wait for the calls to be included.
+    #[clap(long, parse(try_from_str = schedule::parse_duration))]
+    #[serde(serialize_with = "schedule::serialize_duration")]
+    duration: Option<Duration>,
+    /// a TOML file describing the contracts to benchmark, with their constructor and message
+    /// arguments, instead of the built-in contracts. Instance and call counts given there take
+    /// precedence over --instance-count and --call-count.
+    #[clap(long, parse(from_os_str), conflicts_with = "contracts")]
+    scenario: Option<PathBuf>,
+    /// a `.contract` bundle of an ink! or Solang contract to benchmark instead of the built-in
+    /// contracts, calling --message with --args.
+    #[clap(
+        long,
+        parse(from_os_str),
+        conflicts_with_all = &["contracts", "scenario"],
+        requires = "message"
+    )]
+    contract_bundle: Option<PathBuf>,
+    /// the hardhat or foundry artifact (`.json`) of a Solidity contract to benchmark on EVM instead
+    /// of the built-in contracts, calling --message with --args.
+    #[clap(
+        long,
+        parse(from_os_str),
+        conflicts_with_all = &["contracts", "scenario", "contract-bundle"],
+        requires = "message"
+    )]
+    artifact: Option<PathBuf>,
+    /// benchmark the deployment of the contracts instead of calls to them: the --instance-count
+    /// instances of each contract are instantiated while the blocks are measured.
+    #[clap(
+        long,
+        conflicts_with_all = &["call-count", "duration", "mix", "pre-sign", "find-max"]
+    )]
+    deployments: bool,
+    /// upload the code of each Wasm contract once and instantiate all instances from its code
+    /// hash with distinct salts, instead of uploading a unique copy of the code per instance.
+    #[clap(long)]
+    upload_once: bool,
+    /// the storage deposit limit of each call and instantiation of Wasm contracts, unlimited by
+    /// default. The storage deposit charged by the dry runs is checked against it.
+    #[clap(long)]
+    storage_deposit_limit: Option<u128>,
+    /// the constructor to instantiate the --contract-bundle with.
+    #[clap(long, default_value = "new")]
+    constructor: String,
+    /// the constructor arguments as a JSON array, e.g. '[1000000]'.
+    #[clap(long, default_value = "[]")]
+    constructor_args: String,
+    /// the message of the --contract-bundle, or function of the --artifact, to call. Overloaded
+    /// functions are selected by their signature, e.g. 'transfer(address,uint256)'.
+    #[clap(long)]
+    message: Option<String>,
+    /// the message arguments as a JSON array, e.g. '["bob", 1000]'. Accounts can be given as
+    /// SS58 or hex, or by the name of a dev account (`alith` or `balthazar` on EVM).
+    #[clap(long, default_value = "[]")]
+    args: String,
+    /// the value transferred with the instantiation of each contract instance, for payable
+    /// constructors: an amount, e.g. 1000, or a range each amount is drawn from, e.g.
+    /// 1000..2000.
+    #[clap(long, default_value = "0")]
+    constructor_value: CallValue,
+    /// the value transferred with each call, for payable messages: an amount, e.g. 1000, or a
+    /// range the amount of each call is drawn from, e.g. 1000..2000.
+    #[clap(long, default_value = "0")]
+    value: CallValue,
+    /// submit the calls at the given rate, e.g. 500/s or 1200/m, instead of as fast as the node
+    /// accepts them, to measure the throughput the chain sustains.
+    #[clap(long)]
+    rate: Option<Rate>,
+    /// search the maximum rate the chain sustains: the contracts are called for --duration at
+    /// --rate, then at rates increased by --rate-step, until the calls are not included as fast
+    /// as they are submitted, the blocks are full or the client can't keep up.
+    #[clap(long, requires_all = &["rate", "duration"], conflicts_with_all = &["pre-sign", "compare"])]
+    find_max: bool,
+    /// the increase of the rate between the steps of --find-max, defaults to --rate.
+    #[clap(long, requires = "find-max")]
+    rate_step: Option<Rate>,
+    /// the maximum number of steps of --find-max.
+    #[clap(long, default_value = "10")]
+    max_steps: u32,
+    /// the maximum number of calls being submitted at once. The calls are still signed with
+    /// consecutive nonces, in order.
+    #[clap(long, default_value = "1")]
+    max_in_flight: NonZeroUsize,
+    /// call the contracts in a random order, weighted by their share of the calls, e.g.
+    /// erc20=70,storage-read=20,erc721=10. The built-in contracts of the mix are benchmarked if
+    /// none are listed.
+    #[clap(long)]
+    mix: Option<Mix>,
+    /// the seed of the random order of the calls with --mix.
+    #[clap(long, default_value = "0")]
+    seed: u64,
+    /// sign all calls before submitting any of them, spreading the signing across the CPU cores,
+    /// so that only the submission of the calls is measured.
+    #[clap(long, conflicts_with = "duration")]
+    pre_sign: bool,
+    /// the number of accounts to spread the calls across. They are derived from --signer-seed
+    /// and funded by the dev account (alice or alith) before the benchmark.
+    #[clap(long, default_value = "1")]
+    signers: u32,
+    /// the seed the --signers accounts are derived from, as `{seed}//{index}`.
+    #[clap(long, default_value = "//smart-bench")]
+    #[serde(skip)]
+    signer_seed: String,
+    /// how the gas limit of the calls to Wasm contracts is determined.
+    #[clap(long, arg_enum, default_value = "once")]
+    gas_estimate: GasEstimate,
+    /// the margin in percent added to the gas required by the dry runs of --gas-estimate, to
+    /// avoid calls running out of gas.
+    #[clap(long, default_value = "5")]
+    gas_margin: u64,
+    /// the gas limit of the calls with --gas-estimate fixed, as ref_time,proof_size, e.g.
+    /// 5000000000,300000.
+    #[clap(long, required_if_eq("gas-estimate", "fixed"))]
+    gas_limit: Option<GasLimit>,
+    /// the blocks to measure: best blocks, which may later be reorged out, or finalized blocks only.
+    #[clap(long, arg_enum, default_value = "best")]
+    finality: Finality,
+    /// how the block build time for the sTPS calculation is determined.
+    #[clap(long, arg_enum, default_value = "timestamp")]
+    stps_strategy: StpsStrategy,
+    /// the block build time in milliseconds for the slot-time sTPS strategy, also used when the
+    /// blocks don't provide enough data for the selected strategy.
+    #[clap(long, default_value = "12000")]
+    slot_time: u64,
+    /// the format of the benchmark results.
+    #[clap(long, arg_enum, default_value = "text")]
+    #[serde(skip)]
+    output_format: OutputFormat,
+    /// write the benchmark results to the given file instead of the standard output.
+    #[clap(long, parse(from_os_str))]
+    #[serde(skip)]
+    output_file: Option<PathBuf>,
+    /// also append the results as a CSV line to the given file, whatever the --output-format,
+    /// e.g. to record the results of a run compared to a JSON baseline.
+    #[clap(long, parse(from_os_str), conflicts_with = "find-max")]
+    #[serde(skip)]
+    csv_output: Option<PathBuf>,
+    /// the Unix timestamp (in seconds) of the results, e.g. to record several runs at the same
+    /// time. Defaults to the end of the benchmark.
+    #[clap(long)]
+    #[serde(skip)]
+    timestamp: Option<u64>,
+    /// the version of the benchmarked parachain in the results, e.g. the tag of its release.
+    /// Defaults to the spec name and version of its runtime.
+    #[clap(long)]
+    #[serde(skip)]
+    parachain_version: Option<String>,
+    /// compare the results with a previous JSON report (`--output-format json`), failing if they
+    /// regressed by more than --regression-threshold.
+    #[clap(long, parse(from_os_str))]
+    #[serde(skip)]
+    compare: Option<PathBuf>,
+    /// the regression of the sTPS or p90 latency in percent which fails --compare.
+    #[clap(long, default_value = "10")]
+    #[serde(skip)]
+    regression_threshold: f64,
+    /// serve Prometheus metrics of the benchmark progress at the given address, e.g. 127.0.0.1:9615.
+    #[clap(long)]
+    #[serde(skip)]
+    metrics_addr: Option<SocketAddr>,
 }
 
-#[derive(clap::ArgEnum, Debug, Clone)]
+impl Cli {
+    /// Whether the human readable results are printed to stdout, which is not the case if the
+    /// structured report is written there. Progress is always printed to stderr.
+    fn prints_text(&self) -> bool {
+        self.output_format == OutputFormat::Text || self.output_file.is_some()
+    }
+
+    /// The Unix timestamp of the results, which is the current time unless given by --timestamp.
+    fn timestamp(&self) -> color_eyre::Result<u64> {
+        match self.timestamp {
+            Some(timestamp) => Ok(timestamp),
+            None => Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs()),
+        }
+    }
+}
+
+#[derive(clap::ArgEnum, Debug, Clone, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum TargetPlatform {
     InkWasm,
     SolWasm,
     Evm,
 }
 
-#[derive(clap::ArgEnum, Debug, Clone, Eq, PartialEq)]
+#[derive(clap::ArgEnum, Debug, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum Contract {
     Erc20,
     Flipper,
@@ -53,6 +262,54 @@ pub enum Contract {
     StorageReadWrite,
 }
 
+#[derive(clap::ArgEnum, Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Finality {
+    Best,
+    Finalized,
+}
+
+#[derive(clap::ArgEnum, Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StpsStrategy {
+    /// span of the on-chain timestamps of the blocks
+    Timestamp,
+    /// span of the times the blocks were received
+    WallClock,
+    /// the configured --slot-time
+    SlotTime,
+}
+
+#[derive(clap::ArgEnum, Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum GasEstimate {
+    /// dry run the message of each contract instance once while preparing, plus --gas-margin
+    Once,
+    /// dry run every call right before submitting it, plus --gas-margin
+    PerCall,
+    /// the fixed --gas-limit, without dry runs
+    Fixed,
+}
+
+#[derive(clap::ArgEnum, Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// human readable block stats and summary
+    Text,
+    /// structured report with run parameters, block stats and summary
+    Json,
+    /// a single CSV line with the sTPS, appended to the output file
+    Csv,
+    /// a single InfluxDB line protocol point with the sTPS, appended to the output file
+    Influx,
+}
+
+impl OutputFormat {
+    /// Whether results are accumulated in the output file across runs.
+    fn appends(&self) -> bool {
+        matches!(self, OutputFormat::Csv | OutputFormat::Influx)
+    }
+}
+
 impl Display for TargetPlatform {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", clap::ArgEnum::to_possible_value(self).unwrap_or("unknown".into()).get_name())
@@ -65,15 +322,44 @@ impl Display for Contract {
     }
 }
 
+impl Display for StpsStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", clap::ArgEnum::to_possible_value(self).unwrap_or("unknown".into()).get_name())
+    }
+}
+
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
     color_eyre::install()?;
-    let cli = Cli::parse();
-    tracing_subscriber::fmt::init();
+    let mut cli = Cli::parse();
+    tracing_subscriber::fmt().with_writer(std::io::stderr).init();
+
+    if let Some(metrics_addr) = cli.metrics_addr {
+        metrics::serve(metrics_addr).await?;
+    }
 
-    println!("Smart-bench run parameters:");
-    println!("Platform: {}", cli.chain);
-    println!("Contracts: {}", cli.contracts.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().join("+"));
+    if let Some(mix) = &cli.mix {
+        let custom_contracts =
+            cli.scenario.is_some() || cli.contract_bundle.is_some() || cli.artifact.is_some();
+        if cli.contracts.is_empty() && !custom_contracts {
+            cli.contracts = mix.contracts()?;
+        }
+    }
+
+    eprintln!("Smart-bench run parameters:");
+    eprintln!("Platform: {}", cli.chain);
+    if let Some(scenario) = &cli.scenario {
+        eprintln!("Scenario: {}", scenario.display());
+    } else if let Some(contract_bundle) = &cli.contract_bundle {
+        eprintln!("Contract bundle: {}", contract_bundle.display());
+    } else if let Some(artifact) = &cli.artifact {
+        eprintln!("Artifact: {}", artifact.display());
+    } else {
+        eprintln!("Contracts: {}", cli.contracts.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().join("+"));
+    }
+    if let Some(mix) = &cli.mix {
+        eprintln!("Mix: {mix} (seed {})", cli.seed);
+    }
 
     match cli.chain {
         TargetPlatform::InkWasm => wasm::exec(cli).await,
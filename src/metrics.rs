@@ -0,0 +1,188 @@
+use crate::{stats::Utilisation, BlockInfo};
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::TcpListener,
+};
+
+/// The number of most recent blocks the rolling sTPS is calculated over.
+const STPS_WINDOW: usize = 10;
+
+static METRICS: Metrics = Metrics {
+    submitted_txs: AtomicU64::new(0),
+    included_txs: AtomicU64::new(0),
+    latest_block: Mutex::new(None),
+    stps_window: Mutex::new(VecDeque::new()),
+};
+
+/// Progress of the running benchmark, exposed in the Prometheus text format with `--metrics-addr`.
+struct Metrics {
+    submitted_txs: AtomicU64,
+    included_txs: AtomicU64,
+    latest_block: Mutex<Option<BlockMetrics>>,
+    /// `(time_stamp, contract_calls)` of the most recent blocks with contract calls.
+    stps_window: Mutex<VecDeque<(u64, u64)>>,
+}
+
+struct BlockMetrics {
+    number: u32,
+    extrinsics: u64,
+    contract_calls: u64,
+    ref_time_ratio: f64,
+    proof_size_ratio: f64,
+    pov_ratio: f64,
+}
+
+/// Record a benchmark transaction submitted to the node.
+pub fn tx_submitted() {
+    METRICS.submitted_txs.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Record a block received while waiting for the benchmark transactions.
+pub fn block_received(block: &BlockInfo) {
+    let contract_calls = block.contract_call_hashes.len() as u64;
+    METRICS
+        .included_txs
+        .fetch_add(contract_calls, Ordering::Relaxed);
+
+    let utilisation = Utilisation::of(&block.stats);
+    *METRICS.latest_block.lock().unwrap() = Some(BlockMetrics {
+        number: block.stats.number,
+        extrinsics: block.stats.num_extrinsics,
+        contract_calls,
+        ref_time_ratio: utilisation.ref_time,
+        proof_size_ratio: utilisation.proof_size,
+        pov_ratio: utilisation.pov,
+    });
+
+    if contract_calls > 0 {
+        let mut window = METRICS.stps_window.lock().unwrap();
+        window.push_back((block.time_stamp, contract_calls));
+        if window.len() > STPS_WINDOW {
+            window.pop_front();
+        }
+    }
+}
+
+/// sTPS over the blocks in the window. The calls of the first block are excluded, since they were
+/// built before the time span of the window starts.
+fn rolling_stps() -> f64 {
+    let window = METRICS.stps_window.lock().unwrap();
+    match (window.front(), window.back()) {
+        (Some((first, _)), Some((last, _))) if last > first => {
+            let calls = window.iter().skip(1).map(|(_, calls)| calls).sum::<u64>();
+            calls as f64 / ((last - first) as f64 / 1000.0)
+        }
+        _ => 0.0,
+    }
+}
+
+fn render() -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+        let _ = writeln!(out, "# HELP smart_bench_{name} {help}");
+        let _ = writeln!(out, "# TYPE smart_bench_{name} {kind}");
+        let _ = writeln!(out, "smart_bench_{name} {value}");
+    };
+    metric(
+        "submitted_txs_total",
+        "counter",
+        "Number of benchmark transactions submitted to the node.",
+        METRICS.submitted_txs.load(Ordering::Relaxed).to_string(),
+    );
+    metric(
+        "included_txs_total",
+        "counter",
+        "Number of benchmark transactions included in a block.",
+        METRICS.included_txs.load(Ordering::Relaxed).to_string(),
+    );
+    if let Some(block) = METRICS.latest_block.lock().unwrap().as_ref() {
+        metric(
+            "block_number",
+            "gauge",
+            "Number of the latest block.",
+            block.number.to_string(),
+        );
+        metric(
+            "block_extrinsics",
+            "gauge",
+            "Number of extrinsics in the latest block.",
+            block.extrinsics.to_string(),
+        );
+        metric(
+            "block_contract_calls",
+            "gauge",
+            "Number of benchmark transactions in the latest block.",
+            block.contract_calls.to_string(),
+        );
+        metric(
+            "block_ref_time_ratio",
+            "gauge",
+            "Ref time weight of the latest block relative to the max block weight.",
+            block.ref_time_ratio.to_string(),
+        );
+        metric(
+            "block_proof_size_ratio",
+            "gauge",
+            "Proof size weight of the latest block relative to the max block weight.",
+            block.proof_size_ratio.to_string(),
+        );
+        metric(
+            "block_pov_ratio",
+            "gauge",
+            "PoV size of the latest block relative to the max PoV size.",
+            block.pov_ratio.to_string(),
+        );
+    }
+    metric(
+        "stps",
+        "gauge",
+        "sTPS over the most recent blocks with benchmark transactions.",
+        rolling_stps().to_string(),
+    );
+    out
+}
+
+/// Serve the metrics over HTTP at `addr` in the background, for the rest of the benchmark.
+pub async fn serve(addr: SocketAddr) -> color_eyre::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    eprintln!("Serving Prometheus metrics at http://{addr}/metrics");
+
+    tokio::spawn(async move {
+        loop {
+            let mut stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    tracing::warn!("Error accepting metrics connection: {err}");
+                    continue;
+                }
+            };
+            tokio::spawn(async move {
+                // Any request is answered with the metrics, so the request itself is not parsed
+                let mut request = [0u8; 1024];
+                if let Err(err) = stream.read(&mut request).await {
+                    tracing::debug!("Error reading metrics request: {err}");
+                    return;
+                }
+                let body = render();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                if let Err(err) = stream.write_all(response.as_bytes()).await {
+                    tracing::debug!("Error writing metrics response: {err}");
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use serde::Serialize;
+use std::fmt;
+
+/// The selector of Solidity's `Error(string)`, with which `require` and `revert` fail.
+const SOLIDITY_ERROR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// The selector of Solidity's `Panic(uint256)`, e.g. for an overflow or a failed `assert`.
+const SOLIDITY_PANIC: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// How a benchmark call included in a block ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case", tag = "outcome", content = "reason")]
+pub enum CallOutcome {
+    Success,
+    /// The contract reverted, with the reason decoded from its output if it is known.
+    Reverted(Option<RevertReason>),
+    /// The call failed outside of the contract, e.g. with `System::ExtrinsicFailed` or an EVM
+    /// error.
+    DispatchError(String),
+}
+
+impl CallOutcome {
+    pub fn is_success(&self) -> bool {
+        *self == Self::Success
+    }
+}
+
+impl fmt::Display for CallOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Success => write!(f, "success"),
+            Self::Reverted(Some(reason)) => write!(f, "reverted: {reason}"),
+            Self::Reverted(None) => write!(f, "reverted"),
+            Self::DispatchError(error) => write!(f, "dispatch error: {error}"),
+        }
+    }
+}
+
+/// The reason a call reverted with, and where it is known from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevertReason {
+    /// Recorded on chain with the revert, e.g. the message of a Solidity `Error(string)` in the
+    /// `Ethereum::Executed` event.
+    Recorded(String),
+    /// Decoded from replaying the call on the state of the parent of its block. This is best
+    /// effort: the calls before it in the block may have changed the state it reverted on.
+    Replayed(String),
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Recorded(reason) => write!(f, "{reason}"),
+            Self::Replayed(reason) => write!(f, "{reason} (replayed)"),
+        }
+    }
+}
+
+impl Serialize for RevertReason {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// The reason of a revert, decoded from the output of the contract: a Solidity `Error(string)`
+/// or `Panic(uint256)`, or an ink! `LangError`, and the raw output otherwise.
+pub fn revert_reason(output: &[u8]) -> String {
+    if let Some(data) = output.strip_prefix(&SOLIDITY_ERROR) {
+        if let Some(message) = abi_string(data) {
+            return format!("Error({message:?})");
+        }
+    } else if let Some(data) = output.strip_prefix(&SOLIDITY_PANIC) {
+        if let Some(code) = abi_word(data, 0) {
+            return format!("Panic(0x{code:02x})");
+        }
+    } else if output == [1, 1] {
+        // ink! messages return `Result<_, LangError>`, with the only variant at index 1
+        return "LangError::CouldNotReadInput".to_string();
+    }
+    if output.is_empty() {
+        "no output".to_string()
+    } else {
+        let hex = output
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        format!("0x{hex}")
+    }
+}
+
+/// The ABI encoded word at the given index, if it fits in a `u64`.
+fn abi_word(data: &[u8], index: usize) -> Option<u64> {
+    let word = data.get(index * 32..(index + 1) * 32)?;
+    let (high, low) = word.split_at(24);
+    if high.iter().any(|byte| *byte != 0) {
+        return None;
+    }
+    Some(u64::from_be_bytes(low.try_into().ok()?))
+}
+
+/// The ABI encoded string which is the only argument of `data`.
+fn abi_string(data: &[u8]) -> Option<String> {
+    let offset = usize::try_from(abi_word(data, 0)?).ok()?;
+    let data = data.get(offset..)?;
+    let len = usize::try_from(abi_word(data, 0)?).ok()?;
+    let bytes = data.get(32..32 + len)?;
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_revert_reasons() {
+        let mut error = SOLIDITY_ERROR.to_vec();
+        let mut word = |value: u8| {
+            error.extend([0; 31]);
+            error.push(value);
+        };
+        word(32);
+        word(5);
+        error.extend(b"owner");
+        error.extend([0; 27]);
+        assert_eq!(revert_reason(&error), "Error(\"owner\")");
+
+        let mut panic = SOLIDITY_PANIC.to_vec();
+        panic.extend([0; 31]);
+        panic.push(0x11);
+        assert_eq!(revert_reason(&panic), "Panic(0x11)");
+
+        assert_eq!(revert_reason(&[1, 1]), "LangError::CouldNotReadInput");
+        assert_eq!(revert_reason(&[0, 1, 3]), "0x000103");
+        assert_eq!(revert_reason(&[]), "no output");
+    }
+
+    #[test]
+    fn labels_replayed_reasons() {
+        let recorded =
+            CallOutcome::Reverted(Some(RevertReason::Recorded("Error(\"owner\")".into())));
+        let replayed = CallOutcome::Reverted(Some(RevertReason::Replayed("Panic(0x11)".into())));
+        assert_eq!(recorded.to_string(), "reverted: Error(\"owner\")");
+        assert_eq!(replayed.to_string(), "reverted: Panic(0x11) (replayed)");
+        assert_eq!(
+            serde_json::to_string(&replayed).unwrap(),
+            r#"{"outcome":"reverted","reason":"Panic(0x11) (replayed)"}"#
+        );
+    }
+}
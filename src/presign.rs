@@ -0,0 +1,44 @@
+use std::num::NonZeroUsize;
+
+/// Sign the calls with `--pre-sign`, spreading them across the CPU cores. The signed calls are
+/// returned in the order of the given calls, which should already have their nonces assigned.
+pub fn sign_in_parallel<T, S, F>(calls: Vec<T>, sign: F) -> Vec<S>
+where
+    T: Send,
+    S: Send,
+    F: Fn(T) -> S + Sync,
+{
+    let threads = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    let chunk_size = calls.len().div_ceil(threads).max(1);
+    let mut calls = calls.into_iter();
+    let chunks = std::iter::from_fn(|| {
+        let chunk = calls.by_ref().take(chunk_size).collect::<Vec<_>>();
+        (!chunk.is_empty()).then_some(chunk)
+    })
+    .collect::<Vec<_>>();
+
+    let sign = &sign;
+    std::thread::scope(|scope| {
+        let signers = chunks
+            .into_iter()
+            .map(|chunk| scope.spawn(move || chunk.into_iter().map(sign).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        signers
+            .into_iter()
+            .flat_map(|signer| signer.join().expect("Signing thread panicked"))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_calls_in_order() {
+        let calls = (0..1000).collect::<Vec<_>>();
+        let signed = sign_in_parallel(calls, |call| call * 2);
+        assert_eq!(signed, (0..1000).map(|call| call * 2).collect::<Vec<_>>());
+        assert!(sign_in_parallel(Vec::<u32>::new(), |call| call).is_empty());
+    }
+}
@@ -0,0 +1,192 @@
+use crate::stats::{StorageDeposit, Summary};
+use serde::Serialize;
+use std::io::Write;
+
+/// A weight of the runtime, in the two dimensions blocks are limited in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Weight {
+    pub ref_time: u64,
+    pub proof_size: u64,
+}
+
+/// The weight available to the calls in a block, from the runtime's `System::BlockWeights`.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct BlockLimits {
+    /// The maximum total weight of the normal extrinsics of a block.
+    pub max_normal: Weight,
+    /// The weight every extrinsic is charged in addition to its call.
+    pub base_extrinsic: Weight,
+}
+
+impl BlockLimits {
+    /// How many calls consuming the given weight fit in a block, `None` if the weight is zero.
+    pub fn max_calls(&self, consumed: Weight) -> Option<u64> {
+        let by_ref_time = self
+            .max_normal
+            .ref_time
+            .checked_div(consumed.ref_time + self.base_extrinsic.ref_time);
+        let by_proof_size = self
+            .max_normal
+            .proof_size
+            .checked_div(consumed.proof_size + self.base_extrinsic.proof_size);
+        match (by_ref_time, by_proof_size) {
+            (Some(by_ref_time), Some(by_proof_size)) => Some(by_ref_time.min(by_proof_size)),
+            (by_ref_time, by_proof_size) => by_ref_time.or(by_proof_size),
+        }
+    }
+}
+
+/// The weight of the message called on a contract, from the dry runs of the calls to its
+/// instances.
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageProfile {
+    /// The name the calls are reported under.
+    pub contract: String,
+    /// The highest weight consumed by a dry run.
+    pub gas_consumed: Weight,
+    /// The highest weight required by a dry run, which is more than the weight consumed if part
+    /// of it is refunded during the call.
+    pub gas_required: Weight,
+    pub storage_deposit: StorageDeposit,
+    /// How many calls fit in a block by the weight they consume, set by [`WeightProfile::new`].
+    pub max_calls_per_block: Option<u64>,
+}
+
+/// The weight profile of the benchmarked messages, printed before the benchmark and compared
+/// with the calls per block measured by it.
+#[derive(Debug, Serialize)]
+pub struct WeightProfile {
+    pub block_limits: BlockLimits,
+    pub messages: Vec<MessageProfile>,
+}
+
+/// The weight profile compared with the measured calls per block, for the JSON report.
+#[derive(Serialize)]
+pub struct ProfileReport<'a> {
+    #[serde(flatten)]
+    pub profile: &'a WeightProfile,
+    /// How many calls fit in a block, with the share of the calls to each contract measured.
+    pub max_calls_per_block: Option<f64>,
+    pub measured_calls_per_block: Option<f64>,
+}
+
+impl WeightProfile {
+    /// The profile of the given messages, with the calls of each fitting in a block.
+    pub fn new(block_limits: BlockLimits, mut messages: Vec<MessageProfile>) -> Self {
+        for message in &mut messages {
+            message.max_calls_per_block = block_limits.max_calls(message.gas_consumed);
+        }
+        Self {
+            block_limits,
+            messages,
+        }
+    }
+
+    /// How many calls fit in a block with the calls to the contracts mixed as in the summary,
+    /// `None` if a contract wasn't profiled or no calls were measured.
+    fn max_calls_per_block(&self, summary: &Summary) -> Option<f64> {
+        let mut blocks_per_call = 0.0;
+        for (contract, contract_summary) in &summary.contracts {
+            let max_calls = self
+                .messages
+                .iter()
+                .find(|message| &message.contract == contract)?
+                .max_calls_per_block?;
+            blocks_per_call += contract_summary.share / max_calls as f64;
+        }
+        (blocks_per_call > 0.0).then(|| 1.0 / blocks_per_call)
+    }
+
+    /// The profile with the calls per block measured by the benchmark.
+    pub fn report<'a>(&'a self, summary: &Summary) -> ProfileReport<'a> {
+        ProfileReport {
+            profile: self,
+            max_calls_per_block: self.max_calls_per_block(summary),
+            measured_calls_per_block: summary.stps.map(|stps| stps * summary.block_build_time),
+        }
+    }
+
+    /// Write the profile, compared with the calls per block measured in the `summary` if there
+    /// is one.
+    pub fn write_text(&self, w: &mut impl Write, summary: Option<&Summary>) -> std::io::Result<()> {
+        writeln!(w, "\nWeight Profile:")?;
+        writeln!(
+            w,
+            "Block Limits: Normal RefTime={} ProofSize={}, Base Extrinsic RefTime={} ProofSize={}",
+            self.block_limits.max_normal.ref_time,
+            self.block_limits.max_normal.proof_size,
+            self.block_limits.base_extrinsic.ref_time,
+            self.block_limits.base_extrinsic.proof_size
+        )?;
+        for message in &self.messages {
+            write!(
+                w,
+                "  {}: Consumed RefTime={} ProofSize={} Required RefTime={} ProofSize={}",
+                message.contract,
+                message.gas_consumed.ref_time,
+                message.gas_consumed.proof_size,
+                message.gas_required.ref_time,
+                message.gas_required.proof_size
+            )?;
+            match message.max_calls_per_block {
+                Some(max_calls) => write!(w, " Max Calls/Block={max_calls}")?,
+                None => write!(w, " Max Calls/Block=n/a")?,
+            }
+            let measured = summary
+                .and_then(|summary| Some((summary, summary.contracts.get(&message.contract)?)))
+                .and_then(|(summary, contract)| {
+                    contract.stps.map(|stps| stps * summary.block_build_time)
+                });
+            if let Some(measured) = measured {
+                write!(w, " Measured={measured:.2}")?;
+            }
+            writeln!(w)?;
+        }
+        if let Some(summary) = summary {
+            let report = self.report(summary);
+            if let (Some(max), Some(measured)) =
+                (report.max_calls_per_block, report.measured_calls_per_block)
+            {
+                writeln!(
+                    w,
+                    "Calls Per Block: {measured:.2} of the theoretical max {max:.2} ({:.1}%)",
+                    measured / max * 100.0
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_calls_by_the_scarcer_dimension() {
+        let limits = BlockLimits {
+            max_normal: Weight {
+                ref_time: 1_500_000,
+                proof_size: 3_000,
+            },
+            base_extrinsic: Weight {
+                ref_time: 100_000,
+                proof_size: 0,
+            },
+        };
+        let consumed = |ref_time, proof_size| Weight {
+            ref_time,
+            proof_size,
+        };
+        assert_eq!(limits.max_calls(consumed(200_000, 100)), Some(5));
+        assert_eq!(limits.max_calls(consumed(200_000, 1_000)), Some(3));
+        assert_eq!(
+            BlockLimits {
+                base_extrinsic: Weight::default(),
+                ..limits
+            }
+            .max_calls(consumed(500_000, 0)),
+            Some(3)
+        );
+    }
+}
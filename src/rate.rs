@@ -0,0 +1,166 @@
+use futures::{stream, Stream};
+use serde::Serialize;
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// How far the submission may catch up with the rate after falling behind, e.g. when the node is
+/// slow to accept calls.
+const MAX_BURST: Duration = Duration::from_millis(100);
+
+/// The rate calls are submitted at with `--rate`, e.g. `500/s`, `1200/m` or `500` per second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rate {
+    per_second: f64,
+}
+
+impl Rate {
+    pub fn new(per_second: f64) -> Self {
+        Self { per_second }
+    }
+
+    pub fn per_second(&self) -> f64 {
+        self.per_second
+    }
+}
+
+impl FromStr for Rate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (count, unit) = s.split_once('/').unwrap_or((s, "s"));
+        let count: f64 = count
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid rate {s}, expected e.g. 500/s"))?;
+        let unit = match unit.trim() {
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            unit => return Err(format!("Invalid rate unit {unit}, expected s, m or h")),
+        };
+        if !(count.is_finite() && count > 0.0) {
+            return Err(format!(
+                "Invalid rate {s}, expected a positive number of calls"
+            ));
+        }
+        let per_second = count / unit;
+        // the interval between calls must be representable, for the token bucket to wait for it
+        if Duration::try_from_secs_f64(1.0 / per_second).is_err() {
+            return Err(format!("Invalid rate {s}, too few calls"));
+        }
+        Ok(Self { per_second })
+    }
+}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/s", self.per_second)
+    }
+}
+
+impl Serialize for Rate {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Paces the submission of calls to a [`Rate`], independent of how fast they are included in
+/// blocks.
+pub struct TokenBucket {
+    per_second: f64,
+    capacity: f64,
+    /// Negative when calls are waiting for their token.
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: Rate) -> Self {
+        Self::starting_at(rate, Instant::now())
+    }
+
+    fn starting_at(rate: Rate, now: Instant) -> Self {
+        let per_second = rate.per_second();
+        Self {
+            per_second,
+            capacity: (per_second * MAX_BURST.as_secs_f64()).max(1.0),
+            tokens: 1.0,
+            refilled: now,
+        }
+    }
+
+    /// Wait until the next call may be submitted.
+    pub async fn acquire(&mut self) {
+        let wait = self.take(Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Take a token, returning how long to wait until it is available.
+    fn take(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.refilled = now;
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::try_from_secs_f64(-self.tokens / self.per_second).unwrap_or(Duration::MAX)
+        }
+    }
+}
+
+/// Yield the items at the given rate, or as fast as they are consumed without one.
+pub fn paced<I: Iterator>(items: I, rate: Option<Rate>) -> impl Stream<Item = I::Item> {
+    let bucket = rate.map(TokenBucket::new);
+    stream::unfold((items, bucket), |(mut items, mut bucket)| async move {
+        let item = items.next()?;
+        if let Some(bucket) = &mut bucket {
+            bucket.acquire().await;
+        }
+        Some((item, (items, bucket)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt as _;
+
+    #[test]
+    fn parse_rate() {
+        assert_eq!(Rate::from_str("500/s").unwrap().per_second(), 500.0);
+        assert_eq!(Rate::from_str("500").unwrap().per_second(), 500.0);
+        assert_eq!(Rate::from_str("120/m").unwrap().per_second(), 2.0);
+        assert_eq!(Rate::from_str("0.5/s").unwrap().per_second(), 0.5);
+        assert!(Rate::from_str("0/s").is_err());
+        assert!(Rate::from_str("1e-300/s").is_err());
+        assert!(Rate::from_str("500/d").is_err());
+        assert!(Rate::from_str("fast").is_err());
+    }
+
+    #[test]
+    fn token_bucket_paces_calls() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::starting_at(Rate::from_str("10/s").unwrap(), start);
+
+        assert_eq!(bucket.take(start), Duration::ZERO);
+        assert_eq!(bucket.take(start), Duration::from_millis(100));
+        assert_eq!(bucket.take(start), Duration::from_millis(200));
+
+        // after falling behind, only a single token accumulates at this rate
+        let later = start + Duration::from_secs(10);
+        assert_eq!(bucket.take(later), Duration::ZERO);
+        assert_eq!(bucket.take(later), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn paced_yields_all_items() {
+        let items = futures::executor::block_on(paced(0..3, None).collect::<Vec<_>>());
+        assert_eq!(items, [0, 1, 2]);
+    }
+}
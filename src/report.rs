@@ -0,0 +1,293 @@
+use crate::{
+    latency::Latency,
+    profile::ProfileReport,
+    stats::{BlockInfo, Summary},
+    Cli, OutputFormat,
+};
+use color_eyre::eyre;
+use serde::Serialize;
+use std::{borrow::Cow, io::Write};
+use subxt::{
+    backend::{legacy::LegacyRpcMethods, rpc::RpcClient},
+    PolkadotConfig as DefaultConfig,
+};
+
+/// Benchmark results in a machine readable form, so downstream tooling doesn't have to parse
+/// the human readable output.
+#[derive(Serialize)]
+pub struct Report<'a> {
+    /// Unix timestamp (in seconds) of the end of the benchmark.
+    pub timestamp: u64,
+    pub parameters: &'a Cli,
+    pub environment: &'a Environment,
+    pub blocks: &'a [BlockInfo],
+    pub summary: &'a Summary,
+    /// The weight profile of the messages from their dry runs, for Wasm contracts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight_profile: Option<ProfileReport<'a>>,
+}
+
+/// Versions of the benchmarked software, which are not part of the run parameters.
+#[derive(Debug, Default, Serialize)]
+pub struct Environment {
+    /// The `--parachain-version`, e.g. the release tag of the benchmarked parachain, or else its
+    /// runtime spec name and version, e.g. `moonbase-2801`.
+    pub parachain_version: Option<String>,
+    /// The compiler(s) the benchmarked contracts were built with, e.g. `rustc 1.74.1`.
+    pub compiler_version: Option<String>,
+}
+
+impl Environment {
+    pub async fn new(cli: &Cli, compiler_version: Option<String>) -> color_eyre::Result<Self> {
+        if let Some(parachain_version) = &cli.parachain_version {
+            return Ok(Self {
+                parachain_version: Some(parachain_version.clone()),
+                compiler_version,
+            });
+        }
+        let rpc = LegacyRpcMethods::<DefaultConfig>::new(RpcClient::from_url(&cli.url).await?);
+        let runtime_version = rpc.state_get_runtime_version(None).await?;
+        let spec_name = runtime_version
+            .other
+            .get("specName")
+            .and_then(|spec_name| spec_name.as_str())
+            .unwrap_or("unknown");
+        Ok(Self {
+            parachain_version: Some(format!("{spec_name}-{}", runtime_version.spec_version)),
+            compiler_version,
+        })
+    }
+}
+
+impl Report<'_> {
+    /// Write the report to `writer` in the given format.
+    pub fn write(&self, format: OutputFormat, mut writer: impl Write) -> color_eyre::Result<()> {
+        match format {
+            OutputFormat::Text => self.summary.write_text(&mut writer)?,
+            OutputFormat::Json => {
+                serde_json::to_writer_pretty(&mut writer, self)?;
+                writeln!(writer)?;
+            }
+            OutputFormat::Csv => self.write_csv(writer)?,
+            OutputFormat::Influx => self.write_influx(writer)?,
+        }
+        Ok(())
+    }
+
+    /// A single CSV line, in the format expected by `stats/get_graph.sh`:
+    /// `timestamp, platform, parachain version, contracts, sTPS, compiler version, parameters`,
+    /// followed by the p50, p90, p99 and max in block latency and finalized latency in ms. Values
+    /// which are not measured are `n/a`, and fields are quoted if they contain a comma, a quote
+    /// or a line break.
+    fn write_csv(&self, mut writer: impl Write) -> color_eyre::Result<()> {
+        writeln!(writer, "{}", self.csv_line())?;
+        Ok(())
+    }
+
+    fn csv_line(&self) -> String {
+        let not_measured = || "n/a".to_string();
+        let mut fields = vec![
+            self.timestamp.to_string(),
+            self.parameters.chain.to_string(),
+            self.parachain_version().to_string(),
+            self.contracts(),
+            self.summary
+                .stps
+                .map_or_else(not_measured, |stps| format!("{stps:.2}")),
+            self.compiler_version().to_string(),
+            self.workload_parameters(),
+        ];
+        for latency in [
+            self.summary.in_block_latency,
+            self.summary.finalized_latency,
+        ] {
+            match latency {
+                Some(l) => fields
+                    .extend([l.p50_ms, l.p90_ms, l.p99_ms, l.max_ms].map(|ms| format!("{ms:.0}"))),
+                None => fields.extend([(); 4].map(|_| not_measured())),
+            }
+        }
+        fields
+            .iter()
+            .map(|field| csv_field(field))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// A single InfluxDB line protocol point of the `tps` measurement, which is what the Grafana
+    /// dashboards in `stats/grafana-provisioning` are built on.
+    fn write_influx(&self, mut writer: impl Write) -> color_eyre::Result<()> {
+        let mut fields = format!("tx_per_sec={:.2}", self.stps()?);
+        for (prefix, latency) in [
+            ("in_block", self.summary.in_block_latency),
+            ("finalized", self.summary.finalized_latency),
+        ] {
+            if let Some(l) = latency {
+                fields.push_str(&format!(
+                    ",{prefix}_p50_ms={:.0},{prefix}_p90_ms={:.0},{prefix}_p99_ms={:.0},{prefix}_max_ms={:.0}",
+                    l.p50_ms, l.p90_ms, l.p99_ms, l.max_ms
+                ));
+            }
+        }
+        writeln!(
+            writer,
+            "tps,platform=\"{}\",parachain_ver=\"{}\",contract_type=\"{}\",contract_compiler_ver=\"{}\" {fields} {}",
+            escape_tag(&self.parameters.chain.to_string()),
+            escape_tag(self.parachain_version()),
+            escape_tag(&self.contracts()),
+            escape_tag(self.compiler_version()),
+            self.timestamp,
+        )?;
+        Ok(())
+    }
+
+    /// The benchmarked built-in contracts, or else the labels of the contracts of a scenario or
+    /// contract bundle.
+    fn contracts(&self) -> String {
+        if self.parameters.contracts.is_empty() {
+            return self
+                .summary
+                .contracts
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("+");
+        }
+        self.parameters
+            .contracts
+            .iter()
+            .map(|contract| contract.to_string())
+            .collect::<Vec<_>>()
+            .join("+")
+    }
+
+    /// The parameters defining the number of calls, e.g. `--instance-count 1 --call-count 10`.
+    fn workload_parameters(&self) -> String {
+        let mut parameters = Vec::new();
+        if let Some(scenario) = &self.parameters.scenario {
+            parameters.push(format!("--scenario {}", scenario.display()));
+        }
+        if let Some(instance_count) = self.parameters.instance_count {
+            parameters.push(format!("--instance-count {instance_count}"));
+        }
+        if let Some(call_count) = self.parameters.call_count {
+            parameters.push(format!("--call-count {call_count}"));
+        }
+        if let Some(duration) = self.parameters.duration {
+            parameters.push(format!("--duration {}s", duration.as_secs_f64()));
+        }
+        if self.parameters.deployments {
+            parameters.push("--deployments".to_string());
+        }
+        if let Some(mix) = &self.parameters.mix {
+            parameters.push(format!("--mix {mix} --seed {}", self.parameters.seed));
+        }
+        parameters.join(" ")
+    }
+
+    fn parachain_version(&self) -> &str {
+        self.environment
+            .parachain_version
+            .as_deref()
+            .unwrap_or("n/a")
+    }
+
+    fn compiler_version(&self) -> &str {
+        self.environment
+            .compiler_version
+            .as_deref()
+            .unwrap_or("n/a")
+    }
+
+    fn stps(&self) -> color_eyre::Result<f64> {
+        self.summary.stps.ok_or_else(|| {
+            eyre::eyre!("Not enough data to calculate sTPS, consider increasing --call-count value")
+        })
+    }
+}
+
+/// Escape the characters which are special in line protocol tag values.
+fn escape_tag(value: &str) -> String {
+    value
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+/// Quote a CSV field if it contains a separator, a quote or a line break, doubling its quotes.
+fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_csv_fields() {
+        assert_eq!(csv_field("rustc 1.74.1"), "rustc 1.74.1");
+        assert_eq!(csv_field("erc20+flipper"), "erc20+flipper");
+        assert_eq!(csv_field("--scenario a,b.toml"), "\"--scenario a,b.toml\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn csv_columns_read_by_get_graph() {
+        use clap::Parser as _;
+        let cli = Cli::parse_from(["smart-bench", "ink-wasm", "erc20", "flipper", "-i1", "-c10"]);
+        let environment = Environment {
+            parachain_version: Some("v1.4.0".to_string()),
+            compiler_version: Some("rustc 1.74.1+solc 0.8.20".to_string()),
+        };
+        let summary = || {
+            Summary::new(
+                &[],
+                &crate::stps::StpsCalculator::new(
+                    crate::StpsStrategy::SlotTime,
+                    Default::default(),
+                ),
+            )
+        };
+        let unmeasured = summary();
+        let mut measured = summary();
+        measured.stps = Some(42.5);
+        let report = |summary| Report {
+            timestamp: 1700000000,
+            parameters: &cli,
+            environment: &environment,
+            blocks: &[],
+            summary,
+            weight_profile: None,
+        };
+        // `awk -F,` splits on every comma and trims the fields
+        let columns = |line: &str| {
+            line.split(',')
+                .take(6)
+                .map(|field| field.trim().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // a run without sTPS still gets a row
+        let line = report(&unmeasured).csv_line();
+        assert_eq!(
+            columns(&line),
+            [
+                "1700000000",
+                "ink-wasm",
+                "v1.4.0",
+                "erc20+flipper",
+                "n/a",
+                "rustc 1.74.1+solc 0.8.20"
+            ]
+        );
+        assert!(line.ends_with(", n/a, n/a, n/a, n/a, n/a, n/a, n/a, n/a"));
+
+        let line = report(&measured).csv_line();
+        assert_eq!(columns(&line)[4], "42.50");
+        assert_eq!(line.split(", ").count(), 15);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::{value::CallValue, Cli, TargetPlatform};
+use color_eyre::{eyre, Section as _};
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+
+/// Argument value replaced with the index of the contract instance the call is made to, e.g. to
+/// mint a different token with each instance.
+const INSTANCE_PLACEHOLDER: &str = "$instance";
+
+/// How many instances of a contract are created, how many times each of them is called, and the
+/// value transferred with each instantiation and call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Workload {
+    pub instance_count: u32,
+    /// `None` to call the instances until the `--duration` of the run has elapsed, or with
+    /// `--deployments`.
+    pub call_count: Option<u32>,
+    pub constructor_value: CallValue,
+    pub value: CallValue,
+}
+
+impl Workload {
+    pub fn new(
+        instance_count: Option<u32>,
+        call_count: Option<u32>,
+        cli: &Cli,
+    ) -> color_eyre::Result<Self> {
+        if call_count.is_none() && cli.duration.is_none() && !cli.deployments {
+            return Err(eyre::eyre!("Missing the number of calls"))
+                .suggestion("Provide --call-count or --duration, or call_count in the scenario");
+        }
+        Ok(Self {
+            instance_count: instance_count
+                .ok_or_else(|| eyre::eyre!("Missing the number of contract instances"))
+                .suggestion("Provide --instance-count, or instance_count in the scenario")?,
+            call_count,
+            constructor_value: cli.constructor_value,
+            value: cli.value,
+        })
+    }
+
+    /// The workload given with `--instance-count` and `--call-count` or `--duration`, transferring
+    /// `--constructor-value` and `--value`.
+    pub fn from_cli(cli: &Cli) -> color_eyre::Result<Self> {
+        Self::new(cli.instance_count, cli.call_count, cli)
+    }
+}
+
+/// Benchmark workloads described in a TOML file given with `--scenario`, so that benchmarks can
+/// be added or tweaked without recompiling. The contracts are listed per platform, e.g.
+///
+/// ```toml
+/// [[ink-wasm]]
+/// contract = "erc20"
+/// constructor = "new"
+/// constructor_args = [1_000_000]
+/// message = "transfer"
+/// args = ["bob", 1000]
+/// instance_count = 10
+/// call_count = 20
+/// value = "1000..2000"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default)]
+    ink_wasm: Vec<ScenarioContract>,
+    #[serde(default)]
+    sol_wasm: Vec<ScenarioContract>,
+    #[serde(default)]
+    evm: Vec<ScenarioContract>,
+}
+
+/// A contract of a scenario, and the calls made to it.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioContract {
+    /// Name of the contract: the `.contract` bundle in `contracts/ink` or
+    /// `contracts/solidity/wasm`, or the hardhat artifact in `contracts/solidity/evm/contracts`.
+    /// Other contracts are given by the path to their `.contract` bundle, or their hardhat or
+    /// foundry `.json` artifact.
+    pub contract: String,
+    /// Name the calls are reported under, the contract (file) name by default.
+    pub label: Option<String>,
+    /// The constructor to instantiate the contract with. Solidity contracts on EVM have a single
+    /// constructor, so it is ignored there.
+    #[serde(default = "default_constructor")]
+    pub constructor: String,
+    #[serde(default)]
+    pub constructor_args: Vec<Value>,
+    /// The message (or function) to call.
+    pub message: String,
+    #[serde(default)]
+    pub args: Vec<Value>,
+    /// Defaults to `--instance-count`.
+    pub instance_count: Option<u32>,
+    /// Defaults to `--call-count`, or calls until the `--duration` has elapsed.
+    pub call_count: Option<u32>,
+    /// The value transferred with the instantiation of each instance, for payable
+    /// constructors. Defaults to `--constructor-value`.
+    pub constructor_value: Option<CallValue>,
+    /// The value transferred with each call, for payable messages. Defaults to `--value`.
+    pub value: Option<CallValue>,
+}
+
+fn default_constructor() -> String {
+    "new".to_string()
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> color_eyre::Result<Self> {
+        let toml = std::fs::read_to_string(path)
+            .with_note(|| format!("Error reading scenario {}", path.display()))?;
+        toml::from_str(&toml).with_note(|| format!("Error parsing scenario {}", path.display()))
+    }
+
+    /// The contracts to benchmark on the given platform.
+    pub fn contracts(&self, chain: &TargetPlatform) -> color_eyre::Result<&[ScenarioContract]> {
+        let contracts = match chain {
+            TargetPlatform::InkWasm => &self.ink_wasm,
+            TargetPlatform::SolWasm => &self.sol_wasm,
+            TargetPlatform::Evm => &self.evm,
+        };
+        if contracts.is_empty() {
+            return Err(eyre::eyre!("The scenario has no contracts for {chain}"));
+        }
+        Ok(contracts)
+    }
+}
+
+impl ScenarioContract {
+    /// The contract given with `--contract-bundle` or `--artifact`, called with `--message` and
+    /// `--args`.
+    pub fn from_cli(contract: &Path, cli: &Cli) -> color_eyre::Result<Self> {
+        Ok(Self {
+            contract: contract.display().to_string(),
+            label: None,
+            constructor: cli.constructor.clone(),
+            constructor_args: parse_args(&cli.constructor_args).note("--constructor-args")?,
+            message: cli
+                .message
+                .clone()
+                .ok_or_else(|| eyre::eyre!("Missing the message to call"))
+                .suggestion("Provide --message")?,
+            args: parse_args(&cli.args).note("--args")?,
+            instance_count: None,
+            call_count: None,
+            constructor_value: None,
+            value: None,
+        })
+    }
+
+    pub fn label(&self) -> &str {
+        self.label.as_deref().unwrap_or_else(|| {
+            Path::new(&self.contract)
+                .file_stem()
+                .and_then(|name| name.to_str())
+                .unwrap_or(&self.contract)
+        })
+    }
+
+    pub fn workload(&self, cli: &Cli) -> color_eyre::Result<Workload> {
+        let workload = Workload::new(
+            self.instance_count.or(cli.instance_count),
+            self.call_count.or(cli.call_count),
+            cli,
+        )
+        .with_note(|| format!("Contract {} of the scenario", self.label()))?;
+        Ok(Workload {
+            constructor_value: self.constructor_value.unwrap_or(workload.constructor_value),
+            value: self.value.unwrap_or(workload.value),
+            ..workload
+        })
+    }
+
+    /// The message arguments for the call to the contract instance with the given index.
+    pub fn args_for_instance(&self, instance: u32) -> Vec<Value> {
+        self.args
+            .iter()
+            .map(|arg| replace_placeholder(arg, instance))
+            .collect()
+    }
+}
+
+/// Arguments given as a JSON array on the command line.
+fn parse_args(json: &str) -> color_eyre::Result<Vec<Value>> {
+    serde_json::from_str(json)
+        .map_err(|e| eyre::eyre!("Expected a JSON array of arguments, got {json}: {e}"))
+}
+
+fn replace_placeholder(value: &Value, instance: u32) -> Value {
+    match value {
+        Value::String(s) if s == INSTANCE_PLACEHOLDER => Value::from(instance),
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| replace_placeholder(item, instance))
+                .collect(),
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, field)| (name.clone(), replace_placeholder(field, instance)))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
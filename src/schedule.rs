@@ -0,0 +1,408 @@
+use crate::{Cli, Contract};
+use color_eyre::{eyre, Section as _};
+use rand::{
+    distributions::{Distribution as _, WeightedIndex},
+    rngs::StdRng,
+    SeedableRng as _,
+};
+use serde::Serialize;
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// The order the calls are made in, until the call count is reached or the `--duration` of the
+/// run has elapsed.
+///
+/// Yields the index of the contract and of its instance to call next.
+pub struct Schedule {
+    order: Order,
+    duration: Option<Duration>,
+    /// When the first call was made.
+    started: Option<Instant>,
+}
+
+enum Order {
+    RoundRobin(RoundRobin),
+    Weighted(Box<Weighted>),
+}
+
+impl Schedule {
+    /// Each round calls every instance of each contract once, until the call count of each
+    /// contract is reached.
+    pub fn new(
+        contracts: impl IntoIterator<Item = (usize, Option<u32>)>,
+        duration: Option<Duration>,
+    ) -> Self {
+        Self {
+            order: Order::RoundRobin(RoundRobin::new(contracts.into_iter().collect())),
+            duration,
+            started: None,
+        }
+    }
+
+    /// Pick the contract of each call at random by its weight, calling its instances in turn,
+    /// until `call_count` calls have been made in total.
+    pub fn weighted(
+        instance_counts: Vec<usize>,
+        weights: Vec<u32>,
+        call_count: Option<u32>,
+        duration: Option<Duration>,
+        seed: u64,
+    ) -> Self {
+        // contracts without instances can't be called
+        let weights = weights
+            .iter()
+            .zip(&instance_counts)
+            .map(|(weight, instance_count)| if *instance_count > 0 { *weight } else { 0 });
+        Self {
+            order: Order::Weighted(Box::new(Weighted {
+                // fails if none of the contracts can be called
+                contracts: WeightedIndex::new(weights).ok(),
+                next_instances: vec![0; instance_counts.len()],
+                instance_counts,
+                calls_left: call_count,
+                rng: StdRng::seed_from_u64(seed),
+            })),
+            duration,
+            started: None,
+        }
+    }
+
+    /// The schedule of the prepared contracts, given by their label, number of instances and call
+    /// count: weighted by the `--mix`, or else round-robin.
+    pub fn from_cli<'a>(
+        cli: &Cli,
+        contracts: impl IntoIterator<Item = (&'a str, usize, Option<u32>)>,
+    ) -> color_eyre::Result<Self> {
+        let contracts = contracts.into_iter().collect::<Vec<_>>();
+        let Some(mix) = &cli.mix else {
+            return Ok(Self::new(
+                contracts
+                    .iter()
+                    .map(|(_, instance_count, call_count)| (*instance_count, *call_count)),
+                cli.duration,
+            ));
+        };
+        if cli.call_count.is_none() && cli.duration.is_none() {
+            return Err(eyre::eyre!("Missing the number of calls of the --mix"))
+                .suggestion("Provide the total number of calls with --call-count, or --duration");
+        }
+        if let Some((label, _)) = mix
+            .weights
+            .iter()
+            .find(|(label, _)| !contracts.iter().any(|(contract, ..)| contract == label))
+        {
+            return Err(eyre::eyre!(
+                "The --mix contains {label}, which is not benchmarked"
+            ));
+        }
+        let weights = contracts
+            .iter()
+            .map(|(label, ..)| {
+                mix.weight(label)
+                    .ok_or_else(|| eyre::eyre!("The contract {label} is missing from the --mix"))
+                    .suggestion("Give each benchmarked contract a weight, e.g. erc20=70,flipper=30")
+            })
+            .collect::<color_eyre::Result<Vec<_>>>()?;
+        Ok(Self::weighted(
+            contracts
+                .iter()
+                .map(|(_, instance_count, _)| *instance_count)
+                .collect(),
+            weights,
+            cli.call_count,
+            cli.duration,
+            cli.seed,
+        ))
+    }
+}
+
+impl Iterator for Schedule {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let started = *self.started.get_or_insert_with(Instant::now);
+        if self
+            .duration
+            .is_some_and(|duration| started.elapsed() >= duration)
+        {
+            return None;
+        }
+        match &mut self.order {
+            Order::RoundRobin(order) => order.next(),
+            Order::Weighted(order) => order.next(),
+        }
+    }
+}
+
+/// Calls every instance of each contract once per round.
+struct RoundRobin {
+    /// The number of instances and the call count of each contract, `None` to call it until the
+    /// duration has elapsed.
+    contracts: Vec<(usize, Option<u32>)>,
+    max_instance_count: usize,
+    round: u32,
+    instance: usize,
+    contract: usize,
+}
+
+impl RoundRobin {
+    fn new(contracts: Vec<(usize, Option<u32>)>) -> Self {
+        let max_instance_count = contracts
+            .iter()
+            .map(|(instance_count, _)| *instance_count)
+            .max()
+            .unwrap_or_default();
+        Self {
+            contracts,
+            max_instance_count,
+            round: 0,
+            instance: 0,
+            contract: 0,
+        }
+    }
+
+    /// Whether any contract still has calls to make in the current or a later round.
+    fn has_rounds_left(&self) -> bool {
+        self.max_instance_count > 0
+            && self.contracts.iter().any(|(instance_count, call_count)| {
+                *instance_count > 0 && call_count.map_or(true, |count| self.round < count)
+            })
+    }
+
+    fn advance(&mut self) {
+        self.contract += 1;
+        if self.contract == self.contracts.len() {
+            self.contract = 0;
+            self.instance += 1;
+            if self.instance == self.max_instance_count {
+                self.instance = 0;
+                self.round += 1;
+            }
+        }
+    }
+}
+
+impl Iterator for RoundRobin {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.has_rounds_left() {
+            let (contract, instance) = (self.contract, self.instance);
+            let (instance_count, call_count) = self.contracts[contract];
+            let round = self.round;
+            self.advance();
+            if instance < instance_count && call_count.map_or(true, |count| round < count) {
+                return Some((contract, instance));
+            }
+        }
+        None
+    }
+}
+
+/// Picks the contract of each call at random by its weight in the `--mix`.
+struct Weighted {
+    /// `None` if none of the contracts can be called.
+    contracts: Option<WeightedIndex<u32>>,
+    instance_counts: Vec<usize>,
+    /// The instance of each contract to call next.
+    next_instances: Vec<usize>,
+    /// `None` to call the contracts until the duration has elapsed.
+    calls_left: Option<u32>,
+    rng: StdRng,
+}
+
+impl Iterator for Weighted {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.calls_left == Some(0) {
+            return None;
+        }
+        let contract = self.contracts.as_ref()?.sample(&mut self.rng);
+        let instance = self.next_instances[contract];
+        self.next_instances[contract] = (instance + 1) % self.instance_counts[contract];
+        if let Some(calls_left) = &mut self.calls_left {
+            *calls_left -= 1;
+        }
+        Some((contract, instance))
+    }
+}
+
+/// The share of the calls of each contract with `--mix`, e.g. `erc20=70,storage-read=20`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mix {
+    /// The weight of each contract by its label.
+    weights: Vec<(String, u32)>,
+}
+
+impl Mix {
+    fn weight(&self, label: &str) -> Option<u32> {
+        self.weights
+            .iter()
+            .find(|(contract, _)| contract == label)
+            .map(|(_, weight)| *weight)
+    }
+
+    /// The built-in contracts of the mix, to benchmark when none are listed.
+    pub fn contracts(&self) -> color_eyre::Result<Vec<Contract>> {
+        self.weights
+            .iter()
+            .map(|(label, _)| {
+                <Contract as clap::ArgEnum>::from_str(label, false)
+                    .map_err(|_| eyre::eyre!("{label} in the --mix is not a built-in contract"))
+                    .suggestion("Use --scenario for other contracts")
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Mix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let weights = s
+            .split(',')
+            .map(parse_share)
+            .collect::<Result<Vec<_>, _>>()?;
+        if weights.iter().all(|(_, weight)| *weight == 0) {
+            return Err("The weights of the mix should not all be zero".to_string());
+        }
+        Ok(Self { weights })
+    }
+}
+
+/// Parse the share of a contract in the mix, e.g. `erc20=70`.
+fn parse_share(share: &str) -> Result<(String, u32), String> {
+    let (label, weight) = share
+        .split_once('=')
+        .ok_or_else(|| format!("Invalid share {share}, expected e.g. erc20=70"))?;
+    let weight = weight
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid weight of {label}, expected a whole number"))?;
+    Ok((label.trim().to_string(), weight))
+}
+
+impl fmt::Display for Mix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shares = self
+            .weights
+            .iter()
+            .map(|(label, weight)| format!("{label}={weight}"))
+            .collect::<Vec<_>>();
+        write!(f, "{}", shares.join(","))
+    }
+}
+
+impl Serialize for Mix {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Parse a duration such as `90s`, `5m`, `1.5h` or `500ms`, in seconds without a unit.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let unit_start = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (value, unit) = s.split_at(unit_start);
+    let value: f64 = value
+        .parse()
+        .map_err(|_| format!("Invalid duration {s}, expected e.g. 5m"))?;
+    let secs = match unit.trim() {
+        "" | "s" => value,
+        "ms" => value / 1000.0,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        unit => {
+            return Err(format!(
+                "Invalid duration unit {unit}, expected ms, s, m or h"
+            ))
+        }
+    };
+    Duration::try_from_secs_f64(secs).map_err(|_| format!("Invalid duration {s}, too long"))
+}
+
+/// Serialize the `--duration` in seconds.
+pub fn serialize_duration<S: serde::Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serializer.serialize_some(&duration.as_secs_f64()),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calls_each_instance_per_round() {
+        let schedule = Schedule::new([(2, Some(2)), (1, Some(3))], None);
+        assert_eq!(
+            schedule.collect::<Vec<_>>(),
+            [(0, 0), (1, 0), (0, 1), (0, 0), (1, 0), (0, 1), (1, 0)]
+        );
+    }
+
+    #[test]
+    fn stops_after_duration() {
+        let mut schedule = Schedule::new([(1, None)], Some(Duration::from_millis(20)));
+        assert_eq!(schedule.by_ref().take(100).count(), 100);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(schedule.next(), None);
+
+        let mut schedule = Schedule::new([(1, Some(10))], Some(Duration::ZERO));
+        assert_eq!(schedule.next(), None);
+    }
+
+    #[test]
+    fn mixes_calls_by_weight() {
+        let calls = Schedule::weighted(vec![2, 1, 3], vec![70, 30, 0], Some(1000), None, 42)
+            .collect::<Vec<_>>();
+        assert_eq!(calls.len(), 1000);
+        let first = calls.iter().filter(|(contract, _)| *contract == 0).count();
+        assert!((600..800).contains(&first), "{first} calls of 1000");
+        assert!(calls.iter().all(|(contract, _)| *contract != 2));
+        // the instances of a contract are called in turn
+        let instances = calls
+            .iter()
+            .filter(|(contract, _)| *contract == 0)
+            .map(|(_, instance)| *instance);
+        assert!(instances.eq((0..2).cycle().take(first)));
+
+        // the same seed gives the same order
+        let same_seed = Schedule::weighted(vec![2, 1, 3], vec![70, 30, 0], Some(1000), None, 42);
+        assert!(same_seed.eq(calls));
+    }
+
+    #[test]
+    fn parse_mix() {
+        let mix = Mix::from_str("erc20=70, storage-read=20,erc721=10").unwrap();
+        assert_eq!(mix.weight("storage-read"), Some(20));
+        assert_eq!(mix.to_string(), "erc20=70,storage-read=20,erc721=10");
+        assert_eq!(
+            mix.contracts().unwrap(),
+            [Contract::Erc20, Contract::StorageRead, Contract::Erc721]
+        );
+        assert!(Mix::from_str("erc20").is_err());
+        assert!(Mix::from_str("erc20=0.5").is_err());
+        assert!(Mix::from_str("erc20=0").is_err());
+    }
+
+    #[test]
+    fn parse_durations() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1.5h"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration(&format!("{}h", u64::MAX)).is_err());
+    }
+}
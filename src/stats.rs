@@ -1,61 +1,327 @@
-use futures::{future, stream::poll_fn, Future, TryStream, TryStreamExt};
+use crate::{
+    finality::FinalizedBlocks,
+    latency::Latency,
+    outcome::CallOutcome,
+    profile::WeightProfile,
+    report::{Environment, Report},
+    stps::{BlockSample, StpsCalculator},
+    Cli, Finality, OutputFormat, StpsStrategy,
+};
+use color_eyre::eyre;
+use futures::{
+    future,
+    stream::{self, poll_fn},
+    Future, TryStream, TryStreamExt,
+};
+use serde::{ser::SerializeStruct as _, Deserialize, Serialize};
 use std::task::Poll;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
 
-use std::collections::HashSet;
-use std::sync::{Arc, Mutex};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex, Once};
+
+#[derive(Serialize)]
 pub struct BlockInfo {
     // block time stamp
     pub time_stamp: u64,
+    #[serde(serialize_with = "serialize_block_stats")]
     pub stats: blockstats::BlockStats,
-    // list of hashes to look for
+    // list of hashes to look for, of the calls which succeeded
     pub contract_call_hashes: Vec<sp_core::H256>,
+    // number of successful benchmark calls in the block, per contract
+    pub calls_per_contract: BTreeMap<String, u64>,
+    // calls in the block which reverted or failed, and are not counted in the sTPS
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed_calls: Vec<FailedCall>,
+    // storage deposit of the benchmark calls in the block, per contract
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub storage_deposit: BTreeMap<String, StorageDeposit>,
+    // when the block was received
+    #[serde(skip)]
+    pub received: Instant,
+    // when the benchmark calls in the block were submitted
+    #[serde(skip)]
+    pub submitted: Vec<Instant>,
+    // when the block was seen finalized
+    #[serde(skip)]
+    pub finalized: Option<Instant>,
+    // number of submitted benchmark calls not yet included after this block
+    pub backlog: usize,
+}
+
+/// The timestamp of a block, and the benchmarked calls in it, as found by a runner.
+pub struct BlockDetails {
+    pub time_stamp: u64,
+    pub calls: Vec<IncludedCall>,
+}
+
+/// A call included in a block.
+pub struct IncludedCall {
+    pub hash: sp_core::H256,
+    /// The storage deposit the call charged and refunded, from its events.
+    pub storage_deposit: StorageDeposit,
+    pub outcome: CallOutcome,
+}
+
+/// A call which was included in a block, but reverted or failed.
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedCall {
+    pub hash: sp_core::H256,
+    /// The contract the call was made to, `None` if it was not submitted by the benchmark.
+    pub contract: Option<String>,
+    #[serde(flatten)]
+    pub outcome: CallOutcome,
+}
+
+/// Storage deposit charged and refunded by calls to Wasm contracts, from their
+/// `Contracts::StorageDepositTransferredAndHeld` and `StorageDepositTransferredAndReleased`
+/// events or from dry runs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StorageDeposit {
+    pub charged: u128,
+    pub refunded: u128,
+}
+
+impl StorageDeposit {
+    /// The deposit held back, negative if more was refunded than charged.
+    pub fn locked(&self) -> i128 {
+        self.charged as i128 - self.refunded as i128
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.charged == 0 && self.refunded == 0
+    }
+}
+
+impl std::ops::AddAssign for StorageDeposit {
+    fn add_assign(&mut self, other: Self) {
+        self.charged += other.charged;
+        self.refunded += other.refunded;
+    }
+}
+
+impl std::fmt::Display for StorageDeposit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (charged {}, refunded {})",
+            self.locked(),
+            self.charged,
+            self.refunded
+        )
+    }
+}
+
+/// A benchmark call submitted to the node.
+pub struct SubmittedCall {
+    /// The contract the call was made to.
+    pub contract: String,
+    pub submitted: Instant,
+}
+
+/// Benchmark calls submitted to the node which are not yet included in a block, shared between
+/// the submission of the calls and [`collect_block_stats`].
+#[derive(Clone)]
+pub struct PendingCalls(Arc<Mutex<Pending>>);
+
+struct Pending {
+    /// Maps the hash of each call to the contract it was made to.
+    calls: HashMap<sp_core::H256, SubmittedCall>,
+    /// Whether more calls are going to be submitted.
+    submitting: bool,
+    /// Calls found in blocks before their submission returned, e.g. with instant seal.
+    included_early: HashSet<sp_core::H256>,
+}
+
+impl Default for PendingCalls {
+    /// No calls submitted yet, and more to come until [`PendingCalls::finish`].
+    fn default() -> Self {
+        Self(Arc::new(Mutex::new(Pending {
+            calls: HashMap::new(),
+            submitting: true,
+            included_early: HashSet::new(),
+        })))
+    }
+}
+
+impl PendingCalls {
+    pub fn insert(&self, hash: sp_core::H256, call: SubmittedCall) {
+        let mut pending = self.0.lock().unwrap();
+        if pending.included_early.remove(&hash) {
+            tracing::debug!("Call {hash:?} was included before its submission returned");
+        } else {
+            pending.calls.insert(hash, call);
+        }
+    }
+
+    /// All calls have been submitted.
+    pub fn finish(&self) {
+        self.0.lock().unwrap().submitting = false;
+    }
+
+    /// Whether all calls have been submitted and included.
+    fn is_done(&self) -> bool {
+        let pending = self.0.lock().unwrap();
+        !pending.submitting && pending.calls.is_empty()
+    }
 }
 
-/// Subscribes to block stats. Completes once *all* hashes in `remaining_hashes` have been received.
+/// Submits the benchmark calls with `submit_calls` while collecting the blocks with
+/// [`collect_block_stats`], so that blocks are received while the calls are submitted.
+pub fn submit_while_collecting(
+    submit_calls: impl Future<Output = color_eyre::Result<()>>,
+    blocks: impl TryStream<Ok = BlockInfo, Error = color_eyre::Report>,
+) -> impl TryStream<Ok = BlockInfo, Error = color_eyre::Report> {
+    let submit_calls = stream::once(submit_calls).try_filter_map(|()| future::ready(Ok(None)));
+    stream::select(submit_calls, blocks.into_stream())
+}
+
+/// How long to wait for the last benchmark block to be finalized, to measure finalization latency.
+const FINALITY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Subscribes to block stats. Completes once *all* calls in `pending` have been submitted and
+/// received.
+///
+/// With [`Finality::Finalized`] each block is only yielded once it is finalized, and blocks which
+/// are not part of the finalized chain are skipped.
 pub fn collect_block_stats<F, Fut>(
-    block_stats: impl TryStream<Ok = blockstats::BlockStats, Error = subxt::Error> + Unpin,
-    remaining_hashes: HashSet<sp_core::H256>,
+    block_stats: impl TryStream<Ok = blockstats::BlockStats, Error = subxt::Error>
+        + Send
+        + Unpin
+        + 'static,
+    pending: PendingCalls,
+    finality: Finality,
+    finalized_blocks: FinalizedBlocks,
     get_block_details: F,
 ) -> impl TryStream<Ok = BlockInfo, Error = color_eyre::Report>
 where
-    Fut: Future<Output = color_eyre::Result<(u64, Vec<sp_core::H256>)>>,
+    Fut: Future<Output = color_eyre::Result<BlockDetails>>,
     F: Fn(sp_core::H256) -> Fut + Copy,
 {
-    let block_stats_arc = Arc::new(Mutex::new(block_stats));
-    let remaining_hashes_arc = Arc::new(Mutex::new(remaining_hashes));
+    let block_stats_arc = Arc::new(tokio::sync::Mutex::new(receive_blocks(block_stats)));
+    let first_block = Arc::new(Once::new());
 
-    let remaining_hashes = remaining_hashes_arc.clone();
+    let remaining = pending.clone();
     let stream = poll_fn(move |_| -> Poll<Option<Result<(), color_eyre::Report>>> {
-        let remaining_hashes = remaining_hashes.lock().unwrap();
-
-        if !remaining_hashes.is_empty() {
+        if !remaining.is_done() {
             Poll::Ready(Some(Ok(())))
         } else {
             Poll::Ready(None)
         }
     });
 
-    stream.and_then(move |_| {
-        let remaining_hashes = remaining_hashes_arc.clone();
-        let block_stats = block_stats_arc.clone();
-        async move {
-            let stats = block_stats.lock().unwrap().try_next().await?.unwrap();
-            tracing::debug!("{stats:?}");
-            let (time_stamp, hashes) = get_block_details(stats.hash).await?;
-            let mut remaining_hashes = remaining_hashes.lock().unwrap();
-            for xt in &hashes {
-                remaining_hashes.remove(xt);
+    stream
+        .and_then(move |_| {
+            let pending = pending.clone();
+            let block_stats = block_stats_arc.clone();
+            let finalized_blocks = finalized_blocks.clone();
+            let first_block = first_block.clone();
+            async move {
+                let (stats, received) = block_stats
+                    .lock()
+                    .await
+                    .recv()
+                    .await
+                    .ok_or_else(|| eyre::eyre!("Block stats subscription ended"))??;
+                tracing::debug!("{stats:?}");
+                // finalized blocks before the run, e.g. of earlier `--find-max` steps, are not
+                // looked up any more
+                first_block.call_once(|| finalized_blocks.prune_before(stats.number.into()));
+                let finalized = match finality {
+                    Finality::Best => None,
+                    Finality::Finalized => {
+                        let (hash, finalized) = finalized_blocks
+                            .wait_for(stats.number.into())
+                            .await
+                            .ok_or_else(|| eyre::eyre!("Finalized blocks subscription ended"))?;
+                        if hash != stats.hash {
+                            tracing::info!(
+                                "Skipping block {} {:?}, which is not part of the finalized chain",
+                                stats.number,
+                                stats.hash
+                            );
+                            return Ok(None);
+                        }
+                        Some(finalized)
+                    }
+                };
+                let details = get_block_details(stats.hash).await?;
+                let mut pending = pending.0.lock().unwrap();
+                let mut calls_per_contract = BTreeMap::new();
+                let mut storage_deposit = BTreeMap::new();
+                let mut contract_call_hashes = Vec::new();
+                let mut failed_calls = Vec::new();
+                let mut submitted = Vec::new();
+                for xt in details.calls {
+                    let call = pending.calls.remove(&xt.hash);
+                    if call.is_none() && pending.submitting {
+                        pending.included_early.insert(xt.hash);
+                    }
+                    if let Some(call) = &call {
+                        if !xt.storage_deposit.is_zero() {
+                            *storage_deposit
+                                .entry(call.contract.clone())
+                                .or_insert_with(StorageDeposit::default) += xt.storage_deposit;
+                        }
+                    }
+                    if !xt.outcome.is_success() {
+                        failed_calls.push(FailedCall {
+                            hash: xt.hash,
+                            contract: call.map(|call| call.contract),
+                            outcome: xt.outcome,
+                        });
+                        continue;
+                    }
+                    contract_call_hashes.push(xt.hash);
+                    if let Some(call) = call {
+                        *calls_per_contract.entry(call.contract).or_insert(0) += 1;
+                        submitted.push(call.submitted);
+                    }
+                }
+                let block_info = BlockInfo {
+                    time_stamp: details.time_stamp,
+                    contract_call_hashes,
+                    calls_per_contract,
+                    failed_calls,
+                    storage_deposit,
+                    received,
+                    submitted,
+                    finalized,
+                    backlog: pending.calls.len(),
+                    stats,
+                };
+                crate::metrics::block_received(&block_info);
+                Ok(Some(block_info))
+            }
+        })
+        .try_filter_map(|block_info| future::ready(Ok(block_info)))
+}
+
+/// Receives the blocks of the subscription in the background, recording the time each of them was
+/// received. Polling the subscription only once the previous block has been processed would delay
+/// this time by the processing, e.g. by waiting for the finality of the previous block.
+fn receive_blocks(
+    mut block_stats: impl TryStream<Ok = blockstats::BlockStats, Error = subxt::Error>
+        + Send
+        + Unpin
+        + 'static,
+) -> mpsc::UnboundedReceiver<Result<(blockstats::BlockStats, Instant), subxt::Error>> {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(stats) = block_stats.try_next().await.transpose() {
+            let stats = stats.map(|stats| (stats, Instant::now()));
+            if sender.send(stats).is_err() {
+                // the benchmark is done with the blocks
+                break;
             }
-            Ok(BlockInfo {
-                time_stamp,
-                contract_call_hashes: hashes,
-                stats,
-            })
         }
-    })
+    });
+    receiver
 }
 
-/// This function prints statistics to the standard output.
+/// This function prints statistics to the standard output, and writes the report in the format
+/// selected with `--output-format`.
 
 /// The TPS calculation is based on the following assumptions about smart-bench:
 /// - smart-bench instantiates smart contracts on the chain and waits for the completion of these transactions.
@@ -66,63 +332,696 @@ where
 /// TPS calculation is exclusively concerned with contract calls, disregarding any system or contract-creating transactions.
 
 /// TPS calculation excludes the last block of the benchmark, as its full utilization is not guaranteed. In other words, only blocks in the middle will consist entirely of contract calls.
+
+/// The block build time is determined according to `--stps-strategy`, see [`StpsCalculator`].
 pub async fn print_block_info(
+    cli: &Cli,
+    environment: &Environment,
+    finalized_blocks: &FinalizedBlocks,
+    weight_profile: Option<&WeightProfile>,
     block_info: impl TryStream<Ok = BlockInfo, Error = color_eyre::Report>,
 ) -> color_eyre::Result<()> {
-    let mut call_extrinsics_per_block: Vec<u64> = Vec::new();
-    let mut call_block_expected = false;
-    let mut time_stamp_first = None;
-    let mut time_stamp_last = None;
-    println!();
+    // the human readable results are only printed if they aren't mixed with the structured report
+    let print_text = cli.prints_text();
+    let mut blocks = collect_blocks(block_info, print_text).await?;
+
+    wait_for_finality(&mut blocks, finalized_blocks).await;
+
+    let calculator = StpsCalculator::new(cli.stps_strategy, Duration::from_millis(cli.slot_time));
+    let summary = Summary::new(&blocks, &calculator);
+    if print_text {
+        summary.write_text(&mut std::io::stdout())?;
+        if let Some(weight_profile) = weight_profile {
+            weight_profile.write_text(&mut std::io::stdout(), Some(&summary))?;
+        }
+    }
+
+    let report = Report {
+        timestamp: cli.timestamp()?,
+        parameters: cli,
+        environment,
+        blocks: &blocks,
+        summary: &summary,
+        weight_profile: weight_profile.map(|profile| profile.report(&summary)),
+    };
+    match &cli.output_file {
+        Some(path) => {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(cli.output_format.appends())
+                .truncate(!cli.output_format.appends())
+                .open(path)?;
+            report.write(cli.output_format, file)?
+        }
+        None if cli.output_format != OutputFormat::Text => {
+            report.write(cli.output_format, std::io::stdout().lock())?
+        }
+        None => (),
+    }
+    if let Some(path) = &cli.csv_output {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        report.write(OutputFormat::Csv, file)?
+    }
+
+    if let Some(baseline) = &cli.compare {
+        if print_text {
+            crate::compare::compare(cli, baseline, &summary, &mut std::io::stdout())?
+        } else {
+            crate::compare::compare(cli, baseline, &summary, &mut std::io::stderr())?
+        }
+    }
+    Ok(())
+}
+
+/// Collect the blocks of a run, printing their stats as they are received if `print_text`.
+pub async fn collect_blocks(
+    block_info: impl TryStream<Ok = BlockInfo, Error = color_eyre::Report>,
+    print_text: bool,
+) -> color_eyre::Result<Vec<BlockInfo>> {
+    let mut blocks = Vec::new();
+    if print_text {
+        println!();
+    }
     block_info
         .try_for_each(|block| {
-            println!("{}", block.stats);
-            let contract_calls_count = block.contract_call_hashes.len() as u64;
-            // Skip blocks at the beggining until we see first call related transaction
-            // Once first call is seen, we expect all further blocks to contain calls until all calls are covered
-            if !call_block_expected && contract_calls_count > 0 {
-                call_block_expected = true;
-            }
-            if call_block_expected {
-                time_stamp_last = Some(block.time_stamp);
-                if time_stamp_first.is_none() {
-                    time_stamp_first = time_stamp_last;
+            if print_text {
+                println!("{}", block.stats);
+                if block.calls_per_contract.len() > 1 {
+                    println!("      {}", block.contract_shares());
                 }
-
-                call_extrinsics_per_block.push(contract_calls_count);
             }
+            blocks.push(block);
 
             future::ready(Ok(()))
         })
         .await?;
+    Ok(blocks)
+}
+
+/// Record when the blocks with benchmark calls were finalized. Waits for the last of them to be
+/// finalized, unless the chain doesn't finalize blocks.
+async fn wait_for_finality(blocks: &mut [BlockInfo], finalized_blocks: &FinalizedBlocks) {
+    let last_call_block = blocks
+        .iter()
+        .rev()
+        .find(|block| !block.submitted.is_empty())
+        .map(|block| block.stats.number);
+    if let Some(number) = last_call_block {
+        if finalized_blocks.is_progressing() {
+            if finalized_blocks.get(number.into()).is_none() {
+                eprintln!("Waiting for block {number} to be finalized");
+            }
+            let wait = finalized_blocks.wait_for(number.into());
+            if tokio::time::timeout(FINALITY_TIMEOUT, wait).await.is_err() {
+                tracing::warn!("Block {number} not finalized within {FINALITY_TIMEOUT:?}");
+            }
+        } else {
+            tracing::warn!(
+                "The chain is not finalizing blocks, finalization latency is not measured"
+            );
+        }
+    }
 
-    // Skip the last block as it's not stressed to its full capabilities, 
-    // since there is a very low chance of hitting that exact amount of transactions 
-    // (it will contain as many transactions as there are left to execute).
-    let call_extrinsics_per_block =
-        &call_extrinsics_per_block[0..call_extrinsics_per_block.len() - 1];
-
-    let tps_blocks = call_extrinsics_per_block.len();
-    let tps_total_extrinsics = call_extrinsics_per_block.iter().sum::<u64>();
-    let time_diff = time_stamp_first.and_then(|first| {
-        if let Some(last) = time_stamp_last {
-            return Some(last - first)
-        }
-        None
-    }).filter(|&d| d == 0).map(|d| d as f64 / (tps_blocks - 1) as f64 / 1000.0 ).unwrap_or(12.0);
-
-    println!("\nSummary:");
-    println!("Total Blocks: {tps_blocks}");
-    println!("Total Extrinsics: {tps_total_extrinsics}");
-    println!("Block Build Time: {time_diff}");
-    if tps_blocks > 0 {
-        println!("sTPS - Standard Transaction Per Second");
-        println!(
-            "sTPS: {:.2}",
-            tps_total_extrinsics as f64 / (tps_blocks as f64 * time_diff)
+    for block in blocks {
+        block.finalized = finalized_blocks
+            .get(block.stats.number.into())
+            .filter(|(hash, _)| *hash == block.stats.hash)
+            .map(|(_, finalized)| finalized);
+    }
+}
+
+/// Aggregated results of a benchmark run.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub total_blocks: usize,
+    pub total_extrinsics: u64,
+    /// Block build time in seconds.
+    pub block_build_time: f64,
+    /// How the block build time was determined.
+    pub stps_strategy: StpsStrategy,
+    /// Standard Transaction Per Second, `None` if there is not enough data to calculate it.
+    pub stps: Option<f64>,
+    /// Average usage of the block limits by the blocks the sTPS is calculated over.
+    pub block_utilisation: Option<Utilisation>,
+    /// Breakdown of the benchmark calls per contract.
+    pub contracts: BTreeMap<String, ContractSummary>,
+    /// Latency of the benchmark calls from submission until they are included in a block.
+    pub in_block_latency: Option<Latency>,
+    /// Latency of the benchmark calls from submission until they are finalized.
+    pub finalized_latency: Option<Latency>,
+    /// Rate the included calls were submitted at, in calls per second.
+    pub submission_rate: Option<f64>,
+    /// Rate the calls were included at, from the first submission until the last block with
+    /// calls, in calls per second.
+    pub inclusion_rate: Option<f64>,
+    /// The most calls submitted but not yet included after any block.
+    pub max_backlog: usize,
+    /// Storage deposit charged and refunded by the benchmark calls in all blocks.
+    pub storage_deposit: StorageDeposit,
+    /// Calls in all blocks which reverted or failed, and are not counted in the sTPS.
+    pub failed_calls: Failures,
+}
+
+/// Calls which were included in a block, but reverted or failed.
+#[derive(Debug, Default, Serialize)]
+pub struct Failures {
+    pub reverted: u64,
+    pub dispatch_errors: u64,
+    /// The number of failed calls per reason, e.g. `Error("insufficient balance")`.
+    pub reasons: BTreeMap<String, u64>,
+}
+
+impl Failures {
+    fn new(blocks: &[BlockInfo]) -> Self {
+        let mut failures = Self::default();
+        for call in blocks.iter().flat_map(|block| &block.failed_calls) {
+            let reason = match &call.outcome {
+                CallOutcome::Success => continue,
+                CallOutcome::Reverted(reason) => {
+                    failures.reverted += 1;
+                    match reason {
+                        Some(reason) => format!("reverted: {reason}"),
+                        None => "reverted: unknown reason".to_string(),
+                    }
+                }
+                CallOutcome::DispatchError(_) => {
+                    failures.dispatch_errors += 1;
+                    call.outcome.to_string()
+                }
+            };
+            *failures.reasons.entry(reason).or_insert(0) += 1;
+        }
+        failures
+    }
+
+    pub fn total(&self) -> u64 {
+        self.reverted + self.dispatch_errors
+    }
+}
+
+/// Usage of the block limits, as ratio of the limit.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Utilisation {
+    pub ref_time: f64,
+    pub proof_size: f64,
+    pub pov: f64,
+}
+
+impl Utilisation {
+    pub fn of(stats: &blockstats::BlockStats) -> Self {
+        Self {
+            ref_time: ratio(stats.weight.ref_time, stats.max_weight.ref_time),
+            proof_size: ratio(stats.weight.proof_size, stats.max_weight.proof_size),
+            pov: ratio(stats.pov_len, stats.max_pov),
+        }
+    }
+
+    /// The average utilisation of the given blocks, `None` if there are none.
+    fn average(blocks: &[BlockInfo]) -> Option<Self> {
+        if blocks.is_empty() {
+            return None;
+        }
+        let count = blocks.len() as f64;
+        let sum = blocks.iter().map(|block| Self::of(&block.stats)).fold(
+            (0.0, 0.0, 0.0),
+            |(ref_time, proof_size, pov), u| {
+                (
+                    ref_time + u.ref_time,
+                    proof_size + u.proof_size,
+                    pov + u.pov,
+                )
+            },
         );
+        Some(Self {
+            ref_time: sum.0 / count,
+            proof_size: sum.1 / count,
+            pov: sum.2 / count,
+        })
+    }
+}
+
+impl std::fmt::Display for Utilisation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "RefTime={:.1}% ProofSize={:.1}% PoV={:.1}%",
+            self.ref_time * 100.0,
+            self.proof_size * 100.0,
+            self.pov * 100.0
+        )
+    }
+}
+
+fn ratio(value: u64, max: u64) -> f64 {
+    if max == 0 {
+        0.0
     } else {
-        println!("sTPS - Error - not enough data to calculate sTPS, consider increasing --call-count value")
+        value as f64 / max as f64
+    }
+}
+
+/// Results of the calls made to a single contract of a mixed workload.
+#[derive(Debug, Serialize)]
+pub struct ContractSummary {
+    /// Calls included in all blocks of the benchmark.
+    pub included_calls: u64,
+    /// Share of the calls in the blocks the sTPS is calculated over.
+    pub share: f64,
+    /// The part of the sTPS made up by calls to this contract.
+    pub stps: Option<f64>,
+    /// Storage deposit charged and refunded by the calls in all blocks.
+    pub storage_deposit: StorageDeposit,
+    /// Calls in all blocks which reverted or failed.
+    pub failed_calls: u64,
+}
+
+impl BlockInfo {
+    /// Calls per contract with their share of the benchmark calls in the block,
+    /// e.g. `erc20=30(62%) erc1155=18(38%)`.
+    fn contract_shares(&self) -> String {
+        let total = self.calls_per_contract.values().sum::<u64>();
+        self.calls_per_contract
+            .iter()
+            .map(|(contract, calls)| format!("{contract}={calls}({}%)", calls * 100 / total))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl Summary {
+    pub fn new(blocks: &[BlockInfo], calculator: &StpsCalculator) -> Self {
+        let samples = blocks.iter().map(BlockSample::from).collect::<Vec<_>>();
+        let measurement = calculator.calculate(&samples);
+        let measured_blocks = &blocks[measurement.blocks.clone()];
+
+        let mut measured_calls: BTreeMap<&str, u64> = BTreeMap::new();
+        for block in measured_blocks {
+            for (contract, calls) in &block.calls_per_contract {
+                *measured_calls.entry(contract).or_insert(0) += calls;
+            }
+        }
+        let measured_total = measured_calls.values().sum::<u64>();
+
+        let new_contract = |contract: &str| {
+            let measured = measured_calls.get(contract).copied().unwrap_or(0);
+            ContractSummary {
+                included_calls: 0,
+                share: if measured_total > 0 {
+                    measured as f64 / measured_total as f64
+                } else {
+                    0.0
+                },
+                stps: (!measured_blocks.is_empty()).then(|| {
+                    measured as f64 / (measured_blocks.len() as f64 * measurement.block_time)
+                }),
+                storage_deposit: StorageDeposit::default(),
+                failed_calls: 0,
+            }
+        };
+        let mut contracts: BTreeMap<String, ContractSummary> = BTreeMap::new();
+        for block in blocks {
+            for (contract, calls) in &block.calls_per_contract {
+                contracts
+                    .entry(contract.clone())
+                    .or_insert_with(|| new_contract(contract))
+                    .included_calls += calls;
+            }
+            for contract in block
+                .failed_calls
+                .iter()
+                .filter_map(|call| call.contract.as_ref())
+            {
+                contracts
+                    .entry(contract.clone())
+                    .or_insert_with(|| new_contract(contract))
+                    .failed_calls += 1;
+            }
+        }
+        let mut storage_deposit = StorageDeposit::default();
+        for block in blocks {
+            for (contract, deposit) in &block.storage_deposit {
+                if let Some(summary) = contracts.get_mut(contract) {
+                    summary.storage_deposit += *deposit;
+                }
+                storage_deposit += *deposit;
+            }
+        }
+
+        let in_block_latencies = blocks
+            .iter()
+            .flat_map(|block| {
+                block
+                    .submitted
+                    .iter()
+                    .map(|submitted| block.received.saturating_duration_since(*submitted))
+            })
+            .collect();
+        let finalized_latencies = blocks
+            .iter()
+            .filter_map(|block| block.finalized.map(|finalized| (block, finalized)))
+            .flat_map(|(block, finalized)| {
+                block
+                    .submitted
+                    .iter()
+                    .map(move |submitted| finalized.saturating_duration_since(*submitted))
+            })
+            .collect();
+
+        let submitted = blocks
+            .iter()
+            .flat_map(|block| block.submitted.iter().copied())
+            .collect::<Vec<_>>();
+        let last_call_block = blocks
+            .iter()
+            .rev()
+            .find(|block| !block.submitted.is_empty());
+
+        Self {
+            total_blocks: measured_blocks.len(),
+            total_extrinsics: measurement.total_extrinsics,
+            block_build_time: measurement.block_time,
+            stps_strategy: measurement.strategy,
+            stps: measurement.stps,
+            block_utilisation: Utilisation::average(measured_blocks),
+            contracts,
+            in_block_latency: Latency::new(in_block_latencies),
+            finalized_latency: Latency::new(finalized_latencies),
+            submission_rate: submission_rate(&submitted),
+            inclusion_rate: last_call_block
+                .and_then(|block| inclusion_rate(&submitted, block.received)),
+            max_backlog: blocks.iter().map(|block| block.backlog).max().unwrap_or(0),
+            storage_deposit,
+            failed_calls: Failures::new(blocks),
+        }
+    }
+
+    pub fn write_text(&self, w: &mut impl std::io::Write) -> std::io::Result<()> {
+        writeln!(w, "\nSummary:")?;
+        writeln!(w, "Total Blocks: {}", self.total_blocks)?;
+        writeln!(w, "Total Extrinsics: {}", self.total_extrinsics)?;
+        writeln!(w, "Block Build Time: {}", self.block_build_time)?;
+        writeln!(w, "Block Build Time Strategy: {}", self.stps_strategy)?;
+        if let Some(utilisation) = &self.block_utilisation {
+            writeln!(w, "Block Utilisation: {utilisation}")?;
+        }
+        if self.contracts.len() > 1 {
+            writeln!(w, "Contracts:")?;
+            for (contract, summary) in &self.contracts {
+                write!(
+                    w,
+                    "  {contract}: Included Calls={} Share={:.2}%",
+                    summary.included_calls,
+                    summary.share * 100.0
+                )?;
+                if let Some(stps) = summary.stps {
+                    write!(w, " sTPS={stps:.2}")?;
+                }
+                if !summary.storage_deposit.is_zero() {
+                    write!(w, " Deposit={}", summary.storage_deposit.locked())?;
+                }
+                if summary.failed_calls > 0 {
+                    write!(w, " Failed={}", summary.failed_calls)?;
+                }
+                writeln!(w)?;
+            }
+        }
+        match &self.in_block_latency {
+            Some(latency) => writeln!(w, "Latency In Block: {latency}")?,
+            None => writeln!(w, "Latency In Block: n/a")?,
+        }
+        match &self.finalized_latency {
+            Some(latency) => writeln!(w, "Latency Finalized: {latency}")?,
+            None => writeln!(w, "Latency Finalized: n/a")?,
+        }
+        if let (Some(submission_rate), Some(inclusion_rate)) =
+            (self.submission_rate, self.inclusion_rate)
+        {
+            writeln!(w, "Submission Rate: {submission_rate:.2} calls/s")?;
+            writeln!(w, "Inclusion Rate: {inclusion_rate:.2} calls/s")?;
+        }
+        writeln!(w, "Max Backlog: {}", self.max_backlog)?;
+        if self.failed_calls.total() > 0 {
+            writeln!(
+                w,
+                "Failed Calls: {} (reverted {}, dispatch errors {}), not counted in the sTPS",
+                self.failed_calls.total(),
+                self.failed_calls.reverted,
+                self.failed_calls.dispatch_errors
+            )?;
+            for (reason, calls) in &self.failed_calls.reasons {
+                writeln!(w, "  {reason}: {calls}")?;
+            }
+        }
+        if !self.storage_deposit.is_zero() {
+            let included_calls = self
+                .contracts
+                .values()
+                .map(|contract| contract.included_calls)
+                .sum::<u64>();
+            writeln!(
+                w,
+                "Storage Deposit Locked: {}, {} per call",
+                self.storage_deposit,
+                self.storage_deposit.locked() / i128::from(included_calls.max(1))
+            )?;
+        }
+        match self.stps {
+            Some(stps) => {
+                writeln!(w, "sTPS - Standard Transaction Per Second")?;
+                writeln!(w, "sTPS: {stps:.2}")?;
+            }
+            None => writeln!(w, "sTPS - Error - not enough data to calculate sTPS, consider increasing --call-count value")?,
+        }
+        Ok(())
+    }
+}
+
+/// Calls per second over the span of the given submission times, `None` without a span.
+fn submission_rate(submitted: &[Instant]) -> Option<f64> {
+    let first = submitted.iter().min()?;
+    let last = submitted.iter().max()?;
+    let span = last.duration_since(*first).as_secs_f64();
+    (span > 0.0).then(|| (submitted.len() - 1) as f64 / span)
+}
+
+/// Calls per second from the first submission until the calls were all included.
+fn inclusion_rate(submitted: &[Instant], included: Instant) -> Option<f64> {
+    let first = submitted.iter().min()?;
+    let span = included.saturating_duration_since(*first).as_secs_f64();
+    (span > 0.0).then(|| submitted.len() as f64 / span)
+}
+
+/// `blockstats::BlockStats` does not implement `Serialize`, so the fields are written out here.
+fn serialize_block_stats<S>(
+    stats: &blockstats::BlockStats,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let mut state = serializer.serialize_struct("BlockStats", 11)?;
+    state.serialize_field("hash", &stats.hash)?;
+    state.serialize_field("number", &stats.number)?;
+    state.serialize_field("pov_len", &stats.pov_len)?;
+    state.serialize_field("witness_len", &stats.witness_len)?;
+    state.serialize_field("len", &stats.len)?;
+    state.serialize_field("ref_time", &stats.weight.ref_time)?;
+    state.serialize_field("proof_size", &stats.weight.proof_size)?;
+    state.serialize_field("num_extrinsics", &stats.num_extrinsics)?;
+    state.serialize_field("max_pov", &stats.max_pov)?;
+    state.serialize_field("max_ref_time", &stats.max_weight.ref_time)?;
+    state.serialize_field("max_proof_size", &stats.max_weight.proof_size)?;
+    state.end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outcome::RevertReason;
+
+    const SLOT_TIME: Duration = Duration::from_secs(1);
+
+    /// A block without weight or PoV, received `received_ms` after `start`, with the given
+    /// successful calls per contract, all submitted at `start`.
+    fn block(number: u32, start: Instant, received_ms: u64, calls: &[(&str, u64)]) -> BlockInfo {
+        // blockstats has no constructor for its weight type, so a zero weight is decoded
+        let zero_weight =
+            || codec::Decode::decode(&mut &[0u8; 16][..]).expect("zeros decode to a zero weight");
+        let call_count = calls.iter().map(|(_, calls)| calls).sum::<u64>();
+        BlockInfo {
+            time_stamp: 0,
+            stats: blockstats::BlockStats {
+                hash: sp_core::H256::repeat_byte(number as u8),
+                number,
+                pov_len: 0,
+                witness_len: 0,
+                len: 0,
+                weight: zero_weight(),
+                num_extrinsics: call_count,
+                max_pov: 0,
+                max_weight: zero_weight(),
+            },
+            contract_call_hashes: (0..call_count)
+                .map(|call| sp_core::H256::from_low_u64_be(u64::from(number) << 32 | call))
+                .collect(),
+            calls_per_contract: calls
+                .iter()
+                .map(|(contract, calls)| (contract.to_string(), *calls))
+                .collect(),
+            failed_calls: Vec::new(),
+            storage_deposit: BTreeMap::new(),
+            received: start + Duration::from_millis(received_ms),
+            submitted: vec![start; call_count as usize],
+            finalized: None,
+            backlog: 0,
+        }
+    }
+
+    fn summary(blocks: &[BlockInfo]) -> Summary {
+        Summary::new(
+            blocks,
+            &StpsCalculator::new(StpsStrategy::SlotTime, SLOT_TIME),
+        )
+    }
+
+    #[test]
+    fn contract_shares_are_measured_over_the_stps_blocks() {
+        let start = Instant::now();
+        let blocks = [
+            block(1, start, 1000, &[]),
+            block(2, start, 2000, &[("erc20", 6), ("flipper", 2)]),
+            block(3, start, 3000, &[("erc20", 6), ("flipper", 2)]),
+            // the last block with calls is not measured
+            block(4, start, 4000, &[("flipper", 3)]),
+        ];
+        let summary = summary(&blocks);
+
+        assert_eq!(summary.total_blocks, 2);
+        assert_eq!(summary.total_extrinsics, 16);
+        assert_eq!(summary.stps, Some(8.0));
+        let erc20 = &summary.contracts["erc20"];
+        assert_eq!(erc20.included_calls, 12);
+        assert_eq!(erc20.share, 0.75);
+        assert_eq!(erc20.stps, Some(6.0));
+        let flipper = &summary.contracts["flipper"];
+        assert_eq!(flipper.included_calls, 7);
+        assert_eq!(flipper.share, 0.25);
+        assert_eq!(flipper.stps, Some(2.0));
+        assert_eq!(blocks[2].contract_shares(), "erc20=6(75%) flipper=2(25%)");
+    }
+
+    #[test]
+    fn latency_is_measured_from_submission() {
+        let start = Instant::now();
+        let mut blocks = [
+            block(1, start, 100, &[("erc20", 1)]),
+            block(2, start, 200, &[("erc20", 2)]),
+        ];
+        blocks[1].submitted[1] = start + Duration::from_millis(50);
+        blocks[0].finalized = Some(start + Duration::from_millis(1000));
+        let summary = summary(&blocks);
+
+        let in_block = summary.in_block_latency.expect("calls were included");
+        assert_eq!(in_block.calls, 3);
+        assert_eq!(in_block.p50_ms, 150.0);
+        assert_eq!(in_block.max_ms, 200.0);
+        // only the calls of finalized blocks have a finalized latency
+        let finalized = summary.finalized_latency.expect("a block was finalized");
+        assert_eq!(finalized.calls, 1);
+        assert_eq!(finalized.max_ms, 1000.0);
+    }
+
+    #[async_std::test]
+    async fn failed_calls_are_counted_by_reason() {
+        let start = Instant::now();
+        let insufficient_balance = || {
+            CallOutcome::Reverted(Some(RevertReason::Replayed(
+                "Error(\"insufficient balance\")".to_string(),
+            )))
+        };
+        // the calls of each block, by their hash, and whether the benchmark made them
+        let block_calls = [
+            vec![
+                (1, true, CallOutcome::Success),
+                (2, true, insufficient_balance()),
+                (3, true, CallOutcome::Success),
+                (4, true, CallOutcome::Reverted(None)),
+            ],
+            vec![
+                (5, true, insufficient_balance()),
+                (6, true, CallOutcome::Success),
+                // a call not made by the benchmark
+                (
+                    7,
+                    false,
+                    CallOutcome::DispatchError("Contracts::OutOfGas".to_string()),
+                ),
+                (8, true, CallOutcome::Success),
+            ],
+        ];
+        let pending = PendingCalls::default();
+        for (hash, benchmarked, _) in block_calls.iter().flatten() {
+            if *benchmarked {
+                let call = SubmittedCall {
+                    contract: "erc20".to_string(),
+                    submitted: start,
+                };
+                pending.insert(sp_core::H256::from_low_u64_be(*hash), call);
+            }
+        }
+        pending.finish();
+        let block_stats =
+            (1..=2).map(move |number| Ok::<_, subxt::Error>(block(number, start, 0, &[]).stats));
+        let get_block_details = |block_hash: sp_core::H256| {
+            let calls = &block_calls[usize::from(block_hash[0]) - 1];
+            let calls = calls
+                .iter()
+                .map(|(hash, _, outcome)| IncludedCall {
+                    hash: sp_core::H256::from_low_u64_be(*hash),
+                    storage_deposit: StorageDeposit::default(),
+                    outcome: outcome.clone(),
+                })
+                .collect();
+            future::ready(Ok(BlockDetails {
+                time_stamp: 0,
+                calls,
+            }))
+        };
+        let blocks: Vec<BlockInfo> = collect_block_stats(
+            stream::iter(block_stats),
+            pending,
+            Finality::Best,
+            FinalizedBlocks::none(),
+            get_block_details,
+        )
+        .try_collect()
+        .await
+        .expect("all calls are included");
+        let summary = summary(&blocks);
+
+        // the failed calls are not counted in the sTPS
+        assert_eq!(blocks[0].contract_call_hashes.len(), 2);
+        assert_eq!(summary.total_extrinsics, 2);
+        assert_eq!(summary.stps, Some(2.0));
+        assert_eq!(summary.contracts["erc20"].included_calls, 4);
+        assert_eq!(summary.contracts["erc20"].failed_calls, 3);
+        assert_eq!(summary.failed_calls.reverted, 3);
+        assert_eq!(summary.failed_calls.dispatch_errors, 1);
+        assert_eq!(summary.failed_calls.total(), 4);
+        assert_eq!(
+            summary.failed_calls.reasons,
+            BTreeMap::from([
+                ("dispatch error: Contracts::OutOfGas".to_string(), 1),
+                (
+                    "reverted: Error(\"insufficient balance\") (replayed)".to_string(),
+                    2
+                ),
+                ("reverted: unknown reason".to_string(), 1),
+            ])
+        );
     }
-    Ok(())
 }
@@ -0,0 +1,203 @@
+use crate::{BlockInfo, StpsStrategy};
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+/// The data of a block the sTPS is calculated from.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockSample {
+    /// On-chain timestamp of the block in milliseconds.
+    pub time_stamp: u64,
+    /// When the block was received.
+    pub received: Instant,
+    /// Number of benchmark calls in the block.
+    pub calls: u64,
+}
+
+impl From<&BlockInfo> for BlockSample {
+    fn from(block: &BlockInfo) -> Self {
+        Self {
+            time_stamp: block.time_stamp,
+            received: block.received,
+            calls: block.contract_call_hashes.len() as u64,
+        }
+    }
+}
+
+/// The result of the sTPS calculation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    /// Indices of the blocks the sTPS is calculated over.
+    pub blocks: Range<usize>,
+    /// Number of benchmark calls in the measured blocks.
+    pub total_extrinsics: u64,
+    /// Average block build time in seconds.
+    pub block_time: f64,
+    /// The strategy the block build time was determined with. Differs from the configured one if
+    /// there was not enough data for it, and the slot time was used instead.
+    pub strategy: StpsStrategy,
+    /// `None` if there are not enough blocks to calculate the sTPS.
+    pub stps: Option<f64>,
+}
+
+/// Calculates the sTPS of a benchmark run from its blocks.
+#[derive(Debug, Clone, Copy)]
+pub struct StpsCalculator {
+    pub strategy: StpsStrategy,
+    /// Block build time used by [`StpsStrategy::SlotTime`], and as a fallback for the others.
+    pub slot_time: Duration,
+}
+
+impl StpsCalculator {
+    pub fn new(strategy: StpsStrategy, slot_time: Duration) -> Self {
+        Self {
+            strategy,
+            slot_time,
+        }
+    }
+
+    /// Blocks before the first benchmark call are skipped. Once the first call is seen, all
+    /// further blocks are expected to contain calls until all calls are covered.
+    ///
+    /// The last block with calls is not measured, as it's not stressed to its full capabilities
+    /// (it will contain as many calls as there are left to execute). It still marks the end of
+    /// the time span of the measured blocks.
+    pub fn calculate(&self, blocks: &[BlockSample]) -> Measurement {
+        let first = blocks
+            .iter()
+            .position(|block| block.calls > 0)
+            .unwrap_or(blocks.len());
+        let call_blocks = &blocks[first..];
+        let measured = first..first + call_blocks.len().saturating_sub(1);
+
+        let total_extrinsics = blocks[measured.clone()]
+            .iter()
+            .map(|block| block.calls)
+            .sum::<u64>();
+        let (strategy, block_time) = self.block_time(call_blocks);
+        let stps = (!measured.is_empty())
+            .then(|| total_extrinsics as f64 / (measured.len() as f64 * block_time));
+
+        Measurement {
+            blocks: measured,
+            total_extrinsics,
+            block_time,
+            strategy,
+            stps,
+        }
+    }
+
+    /// The average time between the given blocks in seconds, along with the strategy it was
+    /// determined with.
+    fn block_time(&self, call_blocks: &[BlockSample]) -> (StpsStrategy, f64) {
+        let span = match (self.strategy, call_blocks.first(), call_blocks.last()) {
+            (StpsStrategy::Timestamp, Some(first), Some(last)) => Some(Duration::from_millis(
+                last.time_stamp.saturating_sub(first.time_stamp),
+            )),
+            (StpsStrategy::WallClock, Some(first), Some(last)) => {
+                Some(last.received.saturating_duration_since(first.received))
+            }
+            _ => None,
+        };
+        let intervals = call_blocks.len().saturating_sub(1);
+        match span {
+            Some(span) if intervals > 0 && !span.is_zero() => {
+                (self.strategy, span.as_secs_f64() / intervals as f64)
+            }
+            _ => {
+                if self.strategy != StpsStrategy::SlotTime && intervals > 0 {
+                    tracing::warn!(
+                        "Blocks with benchmark calls have no {} span, using the slot time",
+                        self.strategy
+                    );
+                }
+                (StpsStrategy::SlotTime, self.slot_time.as_secs_f64())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SLOT_TIME: Duration = Duration::from_secs(12);
+
+    /// Blocks `block_time_ms` apart, both on-chain and on the wall clock, with the given calls.
+    fn blocks(block_time_ms: u64, calls: &[u64]) -> Vec<BlockSample> {
+        let start = Instant::now();
+        calls
+            .iter()
+            .enumerate()
+            .map(|(i, &calls)| BlockSample {
+                time_stamp: 1_700_000_000_000 + i as u64 * block_time_ms,
+                received: start + Duration::from_millis(i as u64 * block_time_ms),
+                calls,
+            })
+            .collect()
+    }
+
+    fn calculate(strategy: StpsStrategy, blocks: &[BlockSample]) -> Measurement {
+        StpsCalculator::new(strategy, SLOT_TIME).calculate(blocks)
+    }
+
+    #[test]
+    fn timestamp_span_is_used_for_block_time() {
+        let blocks = blocks(6000, &[0, 0, 60, 60, 60, 30]);
+        let measurement = calculate(StpsStrategy::Timestamp, &blocks);
+
+        assert_eq!(measurement.blocks, 2..5);
+        assert_eq!(measurement.total_extrinsics, 180);
+        assert_eq!(measurement.strategy, StpsStrategy::Timestamp);
+        assert_eq!(measurement.block_time, 6.0);
+        assert_eq!(measurement.stps, Some(10.0));
+    }
+
+    #[test]
+    fn wall_clock_is_used_for_block_time() {
+        let mut blocks = blocks(6000, &[40, 40, 40]);
+        // the on-chain timestamps must not be used
+        for block in &mut blocks {
+            block.time_stamp = 0;
+        }
+        let measurement = calculate(StpsStrategy::WallClock, &blocks);
+
+        assert_eq!(measurement.strategy, StpsStrategy::WallClock);
+        assert_eq!(measurement.block_time, 6.0);
+        assert_eq!(measurement.stps, Some(80.0 / 12.0));
+    }
+
+    #[test]
+    fn slot_time_is_used_for_block_time() {
+        let blocks = blocks(6000, &[24, 24, 24]);
+        let measurement = calculate(StpsStrategy::SlotTime, &blocks);
+
+        assert_eq!(measurement.strategy, StpsStrategy::SlotTime);
+        assert_eq!(measurement.block_time, 12.0);
+        assert_eq!(measurement.stps, Some(2.0));
+    }
+
+    #[test]
+    fn falls_back_to_slot_time_without_timestamp_span() {
+        // e.g. instant seal, where blocks may share the timestamp
+        let blocks = blocks(0, &[10, 10, 10]);
+        let measurement = calculate(StpsStrategy::Timestamp, &blocks);
+
+        assert_eq!(measurement.strategy, StpsStrategy::SlotTime);
+        assert_eq!(measurement.block_time, 12.0);
+        assert_eq!(measurement.stps, Some(20.0 / 24.0));
+    }
+
+    #[test]
+    fn not_enough_blocks() {
+        let measurement = calculate(StpsStrategy::Timestamp, &blocks(6000, &[0, 0, 10]));
+        assert_eq!(measurement.blocks, 2..2);
+        assert_eq!(measurement.total_extrinsics, 0);
+        assert_eq!(measurement.stps, None);
+
+        let measurement = calculate(StpsStrategy::Timestamp, &[]);
+        assert_eq!(measurement.blocks, 0..0);
+        assert_eq!(measurement.stps, None);
+    }
+}
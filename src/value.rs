@@ -0,0 +1,113 @@
+use rand::{rngs::StdRng, Rng as _, SeedableRng as _};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// The value transferred with the calls to a contract with `--value`, or with its instantiation
+/// with `--constructor-value`: either a fixed amount, e.g. `1000`, or a range the amount of each
+/// call is drawn from, e.g. `1000..2000` (inclusive).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallValue {
+    Fixed(u128),
+    Range { min: u128, max: u128 },
+}
+
+impl CallValue {
+    /// The value of the call with the given index. Drawn values are the same in every run.
+    pub fn for_call(&self, call: usize) -> u128 {
+        match *self {
+            Self::Fixed(value) => value,
+            Self::Range { min, max } => StdRng::seed_from_u64(call as u64).gen_range(min..=max),
+        }
+    }
+}
+
+impl FromStr for CallValue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |value: &str| {
+            value
+                .trim()
+                .replace('_', "")
+                .parse::<u128>()
+                .map_err(|_| format!("Invalid value {s}, expected e.g. 1000 or 1000..2000"))
+        };
+        match s.split_once("..") {
+            Some((min, max)) => {
+                let (min, max) = (parse(min)?, parse(max)?);
+                if min > max {
+                    return Err(format!("Invalid value range {s}, {min} exceeds {max}"));
+                }
+                Ok(Self::Range { min, max })
+            }
+            None => Ok(Self::Fixed(parse(s)?)),
+        }
+    }
+}
+
+impl fmt::Display for CallValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fixed(value) => write!(f, "{value}"),
+            Self::Range { min, max } => write!(f, "{min}..{max}"),
+        }
+    }
+}
+
+impl Serialize for CallValue {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for CallValue {
+    /// A value of a scenario, as an integer or as a string with a range or an amount too large
+    /// for a TOML integer.
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Amount(u64),
+            Text(String),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Amount(value) => Ok(Self::Fixed(value.into())),
+            Raw::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_values() {
+        assert_eq!(CallValue::from_str("0").unwrap(), CallValue::Fixed(0));
+        assert_eq!(
+            CallValue::from_str("1_000_000").unwrap(),
+            CallValue::Fixed(1_000_000)
+        );
+        assert_eq!(
+            CallValue::from_str("1000..2000").unwrap(),
+            CallValue::Range {
+                min: 1000,
+                max: 2000
+            }
+        );
+        assert!(CallValue::from_str("2000..1000").is_err());
+        assert!(CallValue::from_str("-1").is_err());
+    }
+
+    #[test]
+    fn draws_values_in_range() {
+        let value = CallValue::from_str("1000..2000").unwrap();
+        let values = (0..100)
+            .map(|call| value.for_call(call))
+            .collect::<Vec<_>>();
+        assert!(values.iter().all(|value| (1000..=2000).contains(value)));
+        assert!(values.iter().any(|value| *value != values[0]));
+        assert_eq!(value.for_call(7), value.for_call(7));
+        assert_eq!(CallValue::Fixed(5).for_call(7), 5);
+    }
+}
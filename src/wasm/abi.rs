@@ -0,0 +1,466 @@
+use codec::{Compact, Encode};
+use color_eyre::{eyre, Section as _};
+use scale_info::{
+    form::PortableForm, Field, PortableRegistry, PortableType, TypeDef, TypeDefPrimitive,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use sp_core::crypto::{AccountId32, Ss58Codec as _};
+use sp_keyring::AccountKeyring;
+
+/// The constructors and messages of a contract with the types of their arguments, from the ink!
+/// metadata of its `.contract` bundle. Solang emits metadata in the same format.
+///
+/// Used to encode the call data from argument values given at runtime, e.g. in a scenario.
+pub struct InkAbi {
+    spec: Spec,
+    registry: PortableRegistry,
+}
+
+#[derive(Deserialize)]
+struct RawAbi {
+    spec: Spec,
+    types: Vec<PortableType>,
+}
+
+#[derive(Deserialize)]
+struct Spec {
+    constructors: Vec<CallSpec>,
+    messages: Vec<CallSpec>,
+}
+
+#[derive(Deserialize)]
+struct CallSpec {
+    label: String,
+    selector: String,
+    args: Vec<ArgSpec>,
+}
+
+#[derive(Deserialize)]
+struct ArgSpec {
+    label: String,
+    #[serde(rename = "type")]
+    ty: ArgType,
+}
+
+#[derive(Deserialize)]
+struct ArgType {
+    #[serde(rename = "type")]
+    id: u32,
+}
+
+impl InkAbi {
+    pub fn new(contract: &contract_metadata::ContractMetadata) -> color_eyre::Result<Self> {
+        let abi: RawAbi = serde_json::from_value(Value::Object(contract.abi.clone()))
+            .note("Only ink! metadata version 4 is supported")?;
+        Ok(Self {
+            spec: abi.spec,
+            registry: PortableRegistry { types: abi.types },
+        })
+    }
+
+    /// Call data of the constructor with the given label.
+    pub fn encode_constructor(&self, label: &str, args: &[Value]) -> color_eyre::Result<Vec<u8>> {
+        let constructor = find(&self.spec.constructors, label)
+            .with_note(|| format!("Constructors: {}", labels(&self.spec.constructors)))?;
+        self.encode_call(constructor, args)
+    }
+
+    /// Call data of the message with the given label. Messages of trait implementations, which
+    /// are labeled e.g. `Erc1155::create`, can be given without the trait prefix.
+    pub fn encode_message(&self, label: &str, args: &[Value]) -> color_eyre::Result<Vec<u8>> {
+        let message = find(&self.spec.messages, label)
+            .with_note(|| format!("Messages: {}", labels(&self.spec.messages)))?;
+        self.encode_call(message, args)
+    }
+
+    fn encode_call(&self, call: &CallSpec, args: &[Value]) -> color_eyre::Result<Vec<u8>> {
+        if args.len() != call.args.len() {
+            return Err(eyre::eyre!(
+                "{} expects {} arguments ({}), got {}",
+                call.label,
+                call.args.len(),
+                call.args
+                    .iter()
+                    .map(|arg| arg.label.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                args.len()
+            ));
+        }
+        let mut data = impl_serde::serialize::from_hex(&call.selector)
+            .map_err(|e| eyre::eyre!("Invalid selector of {}: {e:?}", call.label))?;
+        for (spec, value) in call.args.iter().zip(args) {
+            self.encode_value(spec.ty.id, value, &mut data)
+                .with_note(|| format!("Argument {} of {}", spec.label, call.label))?;
+        }
+        Ok(data)
+    }
+
+    fn encode_value(&self, id: u32, value: &Value, out: &mut Vec<u8>) -> color_eyre::Result<()> {
+        let ty = self
+            .registry
+            .resolve(id)
+            .ok_or_else(|| eyre::eyre!("Type {id} not found in metadata"))?;
+        match &ty.type_def {
+            TypeDef::Primitive(primitive) => encode_primitive(primitive, value, out),
+            TypeDef::Composite(_) if is_account_id(&ty.path.segments) && value.is_string() => {
+                out.extend(account(value.as_str().unwrap_or_default())?);
+                Ok(())
+            }
+            TypeDef::Composite(composite) => self.encode_fields(&composite.fields, value, out),
+            TypeDef::Array(array) => {
+                if let Some(bytes) = self.bytes(array.type_param.id, value)? {
+                    if bytes.len() != array.len as usize {
+                        return Err(eyre::eyre!("Expected {} bytes", array.len));
+                    }
+                    out.extend(bytes);
+                    return Ok(());
+                }
+                if let Some(limbs) = self.limbs(array.type_param.id, array.len, value)? {
+                    out.extend(limbs);
+                    return Ok(());
+                }
+                let items = as_array(value, Some(array.len as usize))?;
+                for item in items {
+                    self.encode_value(array.type_param.id, item, out)?;
+                }
+                Ok(())
+            }
+            TypeDef::Sequence(sequence) => {
+                if let Some(bytes) = self.bytes(sequence.type_param.id, value)? {
+                    bytes.encode_to(out);
+                    return Ok(());
+                }
+                let items = as_array(value, None)?;
+                Compact(items.len() as u32).encode_to(out);
+                for item in items {
+                    self.encode_value(sequence.type_param.id, item, out)?;
+                }
+                Ok(())
+            }
+            TypeDef::Tuple(tuple) => {
+                if tuple.fields.is_empty() && value.is_null() {
+                    return Ok(());
+                }
+                let items = as_array(value, Some(tuple.fields.len()))?;
+                for (field, item) in tuple.fields.iter().zip(items) {
+                    self.encode_value(field.id, item, out)?;
+                }
+                Ok(())
+            }
+            TypeDef::Variant(variant) => {
+                // a unit variant by name, or `{ Name = fields }`
+                let (name, fields) = match value {
+                    Value::String(name) => (name.as_str(), &Value::Null),
+                    Value::Object(map) if map.len() == 1 => {
+                        let (name, fields) = map.iter().next().expect("map has one entry");
+                        (name.as_str(), fields)
+                    }
+                    _ => return Err(eyre::eyre!("Expected variant name, got {value}")),
+                };
+                let variant = variant
+                    .variants
+                    .iter()
+                    .find(|variant| variant.name == name)
+                    .ok_or_else(|| eyre::eyre!("Unknown variant {name}"))?;
+                variant.index.encode_to(out);
+                if !variant.fields.is_empty() {
+                    self.encode_fields(&variant.fields, fields, out)?;
+                }
+                Ok(())
+            }
+            TypeDef::Compact(compact) => {
+                let ty = self.registry.resolve(compact.type_param.id);
+                match ty.map(|ty| &ty.type_def) {
+                    Some(TypeDef::Primitive(_)) => Compact(unsigned(value)?).encode_to(out),
+                    _ => return Err(eyre::eyre!("Only compact integers are supported")),
+                }
+                Ok(())
+            }
+            TypeDef::BitSequence(_) => Err(eyre::eyre!("Bit sequences are not supported")),
+        }
+    }
+
+    /// Fields of a struct or enum variant, given by name or in order. A struct with a single
+    /// field can be given as the value of the field, e.g. an `AccountId` as `"bob"`.
+    fn encode_fields(
+        &self,
+        fields: &[Field<PortableForm>],
+        value: &Value,
+        out: &mut Vec<u8>,
+    ) -> color_eyre::Result<()> {
+        match value {
+            Value::Object(map) if fields.iter().all(|field| field.name.is_some()) => {
+                for field in fields {
+                    let name = field.name.as_deref().unwrap_or_default();
+                    let value = map
+                        .get(name)
+                        .ok_or_else(|| eyre::eyre!("Missing field {name}"))?;
+                    self.encode_value(field.ty.id, value, out)?;
+                }
+                Ok(())
+            }
+            value if fields.len() == 1 => self.encode_value(fields[0].ty.id, value, out),
+            value => {
+                let items = as_array(value, Some(fields.len()))?;
+                for (field, item) in fields.iter().zip(items) {
+                    self.encode_value(field.ty.id, item, out)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// A byte array or sequence given as a `0x` prefixed hex string. Accounts are only accepted
+    /// for `AccountId` arguments, so any other string is rejected rather than guessed at.
+    fn bytes(&self, element: u32, value: &Value) -> color_eyre::Result<Option<Vec<u8>>> {
+        let is_u8 = matches!(
+            self.registry.resolve(element).map(|ty| &ty.type_def),
+            Some(TypeDef::Primitive(TypeDefPrimitive::U8))
+        );
+        match value {
+            Value::String(s) if is_u8 => hex(s).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// An integer given for an array of unsigned integers, as used for big integers like the
+    /// `U256` of Solang, encoded as little endian limbs.
+    fn limbs(&self, element: u32, len: u32, value: &Value) -> color_eyre::Result<Option<Vec<u8>>> {
+        let limb_size = match self.registry.resolve(element).map(|ty| &ty.type_def) {
+            Some(TypeDef::Primitive(TypeDefPrimitive::U64)) => 8,
+            Some(TypeDef::Primitive(TypeDefPrimitive::U128)) => 16,
+            _ => return Ok(None),
+        };
+        if value.is_array() {
+            return Ok(None);
+        }
+        let mut bytes = unsigned(value)?.to_le_bytes().to_vec();
+        bytes.resize(limb_size * len as usize, 0);
+        Ok(Some(bytes))
+    }
+}
+
+fn find<'a>(calls: &'a [CallSpec], label: &str) -> color_eyre::Result<&'a CallSpec> {
+    calls
+        .iter()
+        .find(|call| call.label == label)
+        .or_else(|| {
+            calls
+                .iter()
+                .find(|call| call.label.rsplit("::").next() == Some(label))
+        })
+        .ok_or_else(|| eyre::eyre!("{label} not found in contract metadata"))
+}
+
+fn labels(calls: &[CallSpec]) -> String {
+    calls
+        .iter()
+        .map(|call| call.label.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn as_array(value: &Value, len: Option<usize>) -> color_eyre::Result<&Vec<Value>> {
+    match value {
+        Value::Array(items) if len.map_or(true, |len| len == items.len()) => Ok(items),
+        _ => match len {
+            Some(len) => Err(eyre::eyre!("Expected array of {len} values, got {value}")),
+            None => Err(eyre::eyre!("Expected array, got {value}")),
+        },
+    }
+}
+
+fn is_account_id(path: &[String]) -> bool {
+    path.last().map(String::as_str) == Some("AccountId")
+}
+
+fn hex(s: &str) -> color_eyre::Result<Vec<u8>> {
+    let hex = s
+        .strip_prefix("0x")
+        .ok_or_else(|| eyre::eyre!("Expected 0x prefixed hex bytes, got {s}"))
+        .suggestion("Accounts given by name or SS58 address are only accepted for AccountId")?;
+    impl_serde::serialize::from_hex(hex).map_err(|e| eyre::eyre!("Invalid hex {s}: {e:?}"))
+}
+
+/// An account given by SS58 address, the name of a development account, e.g. `bob`, or as a
+/// `0x` prefixed hex string of the 32 bytes.
+fn account(s: &str) -> color_eyre::Result<[u8; 32]> {
+    if s.starts_with("0x") {
+        return hex(s)?
+            .try_into()
+            .map_err(|_| eyre::eyre!("Expected 32 bytes for account {s}"));
+    }
+    let keyring = match s.to_lowercase().as_str() {
+        "alice" => AccountKeyring::Alice,
+        "bob" => AccountKeyring::Bob,
+        "charlie" => AccountKeyring::Charlie,
+        "dave" => AccountKeyring::Dave,
+        "eve" => AccountKeyring::Eve,
+        "ferdie" => AccountKeyring::Ferdie,
+        "one" => AccountKeyring::One,
+        "two" => AccountKeyring::Two,
+        _ => {
+            let account = AccountId32::from_ss58check(s)
+                .map_err(|e| eyre::eyre!("Invalid account {s}: {e:?}"))?;
+            return Ok(account.into());
+        }
+    };
+    Ok(keyring.to_raw_public())
+}
+
+/// Integers can be given as numbers, or as strings for values not representable in TOML or JSON.
+fn integer_str(value: &Value) -> color_eyre::Result<String> {
+    match value {
+        Value::Number(n) if n.is_i64() || n.is_u64() => Ok(n.to_string()),
+        Value::String(s) => Ok(s.replace('_', "")),
+        _ => Err(eyre::eyre!("Expected integer, got {value}")),
+    }
+}
+
+fn unsigned(value: &Value) -> color_eyre::Result<u128> {
+    let s = integer_str(value)?;
+    s.parse()
+        .map_err(|e| eyre::eyre!("Invalid unsigned integer {s}: {e}"))
+}
+
+fn signed(value: &Value) -> color_eyre::Result<i128> {
+    let s = integer_str(value)?;
+    s.parse()
+        .map_err(|e| eyre::eyre!("Invalid integer {s}: {e}"))
+}
+
+fn encode_primitive(
+    primitive: &TypeDefPrimitive,
+    value: &Value,
+    out: &mut Vec<u8>,
+) -> color_eyre::Result<()> {
+    fn convert<T: TryFrom<V>, V: std::fmt::Display + Copy>(v: V) -> color_eyre::Result<T> {
+        T::try_from(v).map_err(|_| eyre::eyre!("{v} is out of range"))
+    }
+    match primitive {
+        TypeDefPrimitive::Bool => value
+            .as_bool()
+            .ok_or_else(|| eyre::eyre!("Expected bool, got {value}"))?
+            .encode_to(out),
+        TypeDefPrimitive::Str => value
+            .as_str()
+            .ok_or_else(|| eyre::eyre!("Expected string, got {value}"))?
+            .encode_to(out),
+        TypeDefPrimitive::U8 => convert::<u8, _>(unsigned(value)?)?.encode_to(out),
+        TypeDefPrimitive::U16 => convert::<u16, _>(unsigned(value)?)?.encode_to(out),
+        TypeDefPrimitive::U32 => convert::<u32, _>(unsigned(value)?)?.encode_to(out),
+        TypeDefPrimitive::U64 => convert::<u64, _>(unsigned(value)?)?.encode_to(out),
+        TypeDefPrimitive::U128 => unsigned(value)?.encode_to(out),
+        TypeDefPrimitive::I8 => convert::<i8, _>(signed(value)?)?.encode_to(out),
+        TypeDefPrimitive::I16 => convert::<i16, _>(signed(value)?)?.encode_to(out),
+        TypeDefPrimitive::I32 => convert::<i32, _>(signed(value)?)?.encode_to(out),
+        TypeDefPrimitive::I64 => convert::<i64, _>(signed(value)?)?.encode_to(out),
+        TypeDefPrimitive::I128 => signed(value)?.encode_to(out),
+        TypeDefPrimitive::U256 => {
+            let mut bytes = unsigned(value)?.to_le_bytes().to_vec();
+            bytes.resize(32, 0);
+            out.extend(bytes)
+        }
+        TypeDefPrimitive::Char | TypeDefPrimitive::I256 => {
+            return Err(eyre::eyre!("{primitive:?} arguments are not supported"))
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasm::runner::load_contract;
+    use serde_json::json;
+
+    fn abi(path: &str, name: &str) -> InkAbi {
+        InkAbi::new(&load_contract(path, name).unwrap()).unwrap()
+    }
+
+    fn call_data(selector: [u8; 4], args: impl Encode) -> Vec<u8> {
+        let mut data = selector.to_vec();
+        args.encode_to(&mut data);
+        data
+    }
+
+    #[test]
+    fn encodes_erc20_calls() {
+        let erc20 = abi("contracts/ink", "erc20");
+        let bob = AccountKeyring::Bob.to_raw_public();
+
+        assert_eq!(
+            erc20
+                .encode_constructor("new", &[json!(1_000_000_000)])
+                .unwrap(),
+            call_data([0x9b, 0xae, 0x9d, 0x5e], 1_000_000_000u128)
+        );
+        let transfer = call_data([0x84, 0xa1, 0x5d, 0xa1], (bob, 1000u128));
+        for to in [
+            json!("bob"),
+            json!("5FHneW46xGXgs5mUiveU4sbTyGBzmstUspZC92UhjJM694ty"),
+            json!(impl_serde::serialize::to_hex(&bob, false)),
+        ] {
+            assert_eq!(
+                erc20
+                    .encode_message("transfer", &[to, json!(1000)])
+                    .unwrap(),
+                transfer
+            );
+        }
+        assert!(erc20.encode_message("transfer", &[json!("bob")]).is_err());
+        assert!(erc20
+            .encode_message("transfer", &[json!("0x0102"), json!(1000)])
+            .is_err());
+    }
+
+    #[test]
+    fn encodes_signed_integers() {
+        let computation = abi("contracts/ink", "computation");
+        assert_eq!(
+            computation
+                .encode_message("triangle_number", &[json!(-3)])
+                .unwrap(),
+            call_data([0x8e, 0x70, 0x88, 0x51], -3i32)
+        );
+        assert!(computation
+            .encode_message("triangle_number", &[json!(1u64 << 40)])
+            .is_err());
+    }
+
+    #[test]
+    fn encodes_solang_u256_as_limbs() {
+        let erc20 = abi("contracts/solidity/wasm", "BenchERC20");
+        let mut supply = [0u8; 32];
+        supply[..16].copy_from_slice(&1_000_000_000u128.to_le_bytes());
+        assert_eq!(
+            erc20
+                .encode_constructor("new", &[json!(1_000_000_000)])
+                .unwrap(),
+            call_data([0x58, 0x16, 0xc4, 0x25], supply)
+        );
+    }
+
+    #[test]
+    fn bytes_must_be_hex() {
+        let erc1155 = abi("contracts/ink", "erc1155");
+        let args = |data| [json!("alice"), json!("bob"), json!(1), json!(10), data];
+        let alice = AccountKeyring::Alice.to_raw_public();
+        let bob = AccountKeyring::Bob.to_raw_public();
+
+        assert_eq!(
+            erc1155
+                .encode_message("safe_transfer_from", &args(json!("0x0102")))
+                .unwrap(),
+            call_data(
+                [0x53, 0x24, 0xd5, 0x56],
+                (alice, bob, 1u128, 10u128, vec![1u8, 2])
+            )
+        );
+        assert!(erc1155
+            .encode_message("safe_transfer_from", &args(json!("bob")))
+            .is_err());
+    }
+}
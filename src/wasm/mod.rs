@@ -1,7 +1,16 @@
+mod abi;
 pub mod runner;
 mod xts;
 
-use crate::{Cli, Contract, TargetPlatform};
+use crate::{
+    finality::FinalizedBlocks,
+    find_max::FindMax,
+    report::Environment,
+    scenario::{Scenario, ScenarioContract, Workload},
+    schedule::Schedule,
+    Cli, Contract, TargetPlatform,
+};
+use color_eyre::{eyre, Section as _};
 use sp_core::sr25519;
 use sp_keyring::AccountKeyring;
 use subxt::{tx::PairSigner, utils::AccountId32, PolkadotConfig as DefaultConfig};
@@ -13,6 +22,12 @@ pub type Hash = <DefaultConfig as subxt::Config>::Hash;
 pub type Signer = PairSigner<DefaultConfig, sr25519::Pair>;
 pub type EventRecord = ();
 
+const INK_CONTRACTS_PATH: &str = "contracts/ink";
+const SOLIDITY_CONTRACTS_PATH: &str = "contracts/solidity/wasm";
+
+/// The balance each of the `--signers` is funded with, 100 units of a chain with 12 decimals.
+const SIGNER_FUNDS: Balance = 100_000_000_000_000;
+
 /// Trait implemented by [`smart_bench_macro::contract`] for all contract constructors.
 pub trait InkConstructor: codec::Encode {
     const SELECTOR: [u8; 4];
@@ -46,31 +61,168 @@ mod ink_contracts {
 }
 
 pub async fn exec(cli: Cli) -> color_eyre::Result<()> {
+    if cli.artifact.is_some() {
+        return Err(eyre::eyre!(
+            "--artifact is only supported for EVM contracts"
+        ))
+        .suggestion("Provide the bundle of a Wasm contract with --contract-bundle");
+    }
+
     let alice = PairSigner::new(AccountKeyring::Alice.pair());
     let bob: AccountId32 = AccountKeyring::Bob.to_account_id().into();
 
-    let mut runner = runner::BenchRunner::new(alice, &cli.url).await?;
+    let gas_strategy = runner::GasStrategy::from_cli(&cli)?;
+    let mut runner = runner::BenchRunner::new(
+        alice,
+        &cli.url,
+        cli.finality,
+        gas_strategy,
+        cli.upload_once,
+        cli.deployments,
+        cli.storage_deposit_limit,
+    )
+    .await?;
+
+    if cli.signers > 1 {
+        let signers = (0..cli.signers)
+            .map(|index| derive_signer(&cli.signer_seed, index))
+            .collect::<color_eyre::Result<Vec<_>>>()?;
+        runner.fund_call_signers(signers, SIGNER_FUNDS).await?;
+    }
 
-    match cli.chain {
-        TargetPlatform::SolWasm => prepare_solidity_contracts(&cli, &mut runner, bob).await?,
-        TargetPlatform::InkWasm => prepare_ink_contracts(&cli, &mut runner, bob).await?,
+    let path = match cli.chain {
+        TargetPlatform::SolWasm => SOLIDITY_CONTRACTS_PATH,
+        TargetPlatform::InkWasm => INK_CONTRACTS_PATH,
         _ => panic!("Not supported target platform!"),
+    };
+    if let Some(scenario) = &cli.scenario {
+        let scenario = Scenario::load(scenario)?;
+        prepare_scenario_contracts(&cli, scenario.contracts(&cli.chain)?, path, &mut runner)
+            .await?;
+    } else if let Some(contract_bundle) = &cli.contract_bundle {
+        let contract = ScenarioContract::from_cli(contract_bundle, &cli)?;
+        prepare_scenario_contracts(&cli, &[contract], path, &mut runner).await?;
+    } else {
+        match cli.chain {
+            TargetPlatform::SolWasm => prepare_solidity_contracts(&cli, &mut runner, bob).await?,
+            TargetPlatform::InkWasm => prepare_ink_contracts(&cli, &mut runner, bob).await?,
+            _ => panic!("Not supported target platform!"),
+        }
+    }
+    let environment = Environment::new(&cli, runner.compiler_version()).await?;
+    let weight_profile = runner.weight_profile()?;
+    if let Some(weight_profile) = &weight_profile {
+        if cli.prints_text() {
+            weight_profile.write_text(&mut std::io::stdout(), None)?;
+        } else {
+            weight_profile.write_text(&mut std::io::stderr(), None)?;
+        }
+    }
+    let finalized_blocks = FinalizedBlocks::subscribe(&cli.url).await?;
+    if cli.deployments {
+        let result = runner
+            .run_deployments(finalized_blocks.clone(), cli.rate, cli.max_in_flight)
+            .await?;
+        return crate::print_block_info(&cli, &environment, &finalized_blocks, None, result).await;
+    }
+    if cli.find_max {
+        let mut find_max = FindMax::new(&cli)?;
+        while let Some(rate) = find_max.next_rate() {
+            let schedule = Schedule::from_cli(&cli, runner.workloads())?;
+            let result = runner
+                .run(
+                    finalized_blocks.clone(),
+                    schedule,
+                    Some(rate),
+                    cli.max_in_flight,
+                    false,
+                )
+                .await?;
+            find_max.record(rate, result).await?;
+        }
+        return find_max.write_results(&cli, &environment);
     }
-    let result = runner.run(cli.call_count).await?;
+    let schedule = Schedule::from_cli(&cli, runner.workloads())?;
+    let result = runner
+        .run(
+            finalized_blocks.clone(),
+            schedule,
+            cli.rate,
+            cli.max_in_flight,
+            cli.pre_sign,
+        )
+        .await?;
 
-    crate::print_block_info(result).await?;
+    crate::print_block_info(
+        &cli,
+        &environment,
+        &finalized_blocks,
+        weight_profile.as_ref(),
+        result,
+    )
+    .await?;
 
     Ok(())
 }
 
+/// The account with the given index derived from the `--signer-seed`, as `{seed}//{index}`.
+fn derive_signer(seed: &str, index: u32) -> color_eyre::Result<Signer> {
+    let pair = <sr25519::Pair as sp_core::Pair>::from_string(&format!("{seed}//{index}"), None)
+        .map_err(|e| eyre::eyre!("Invalid --signer-seed {seed}: {e:?}"))?;
+    Ok(PairSigner::new(pair))
+}
+
+/// Prepare the contracts of a scenario, encoding the constructor and message arguments from the
+/// contract metadata.
+pub async fn prepare_scenario_contracts(
+    cli: &Cli,
+    contracts: &[ScenarioContract],
+    path: &str,
+    runner: &mut runner::BenchRunner,
+) -> color_eyre::Result<()> {
+    for contract in contracts {
+        let workload = contract.workload(cli)?;
+        let abi = abi::InkAbi::new(&runner::load_contract(path, &contract.contract)?)?;
+        let constructor = runner::EncodedMessage::from_call_data(
+            abi.encode_constructor(&contract.constructor, &contract.constructor_args)?,
+        );
+        let messages = (0..workload.instance_count)
+            .map(|instance| {
+                let args = contract.args_for_instance(instance);
+                let call_data = abi.encode_message(&contract.message, &args)?;
+                Ok(runner::EncodedMessage::from_call_data(call_data))
+            })
+            .collect::<color_eyre::Result<Vec<_>>>()?;
+        let mut messages = messages.into_iter();
+        let create_message = || {
+            messages
+                .next()
+                .expect("a message is encoded for each instance")
+        };
+        runner
+            .prepare_contract(
+                contract.label(),
+                path,
+                &contract.contract,
+                constructor,
+                workload,
+                create_message,
+            )
+            .await?;
+    }
+    Ok(())
+}
+
 pub async fn prepare_solidity_contracts(
     cli: &Cli,
     runner: &mut runner::BenchRunner,
     bob: AccountId32,
 ) -> color_eyre::Result<()> {
     use solidity_contracts::*;
-    let path = "contracts/solidity/wasm";
+    let path = SOLIDITY_CONTRACTS_PATH;
+    let workload = Workload::from_cli(cli)?;
     for contract in &cli.contracts {
+        let label = contract.to_string();
         match contract {
             Contract::Erc20 => {
                 let erc20_new = BenchERC20::constructors::new(1_000_000.into());
@@ -78,10 +230,11 @@ pub async fn prepare_solidity_contracts(
                     || BenchERC20::messages::transfer(bob.clone(), 1000.into()).into();
                 runner
                     .prepare_contract(
+                        &label,
                         path,
                         "BenchERC20",
                         erc20_new,
-                        cli.instance_count,
+                        workload,
                         &erc20_transfer,
                     )
                     .await?;
@@ -91,10 +244,11 @@ pub async fn prepare_solidity_contracts(
                 let flipper_flip = || flipper::messages::flip().into();
                 runner
                     .prepare_contract(
+                        &label,
                         path,
                         "flipper",
                         flipper_new,
-                        cli.instance_count,
+                        workload,
                         &flipper_flip,
                     )
                     .await?;
@@ -104,10 +258,11 @@ pub async fn prepare_solidity_contracts(
                 let incrementer_increment = || incrementer::messages::inc(1).into();
                 runner
                     .prepare_contract(
+                        &label,
                         path,
                         "incrementer",
                         incrementer_new,
-                        cli.instance_count,
+                        workload,
                         incrementer_increment,
                     )
                     .await?;
@@ -122,10 +277,11 @@ pub async fn prepare_solidity_contracts(
                 };
                 runner
                     .prepare_contract(
+                        &label,
                         path,
                         "BenchERC721",
                         erc721_new,
-                        cli.instance_count,
+                        workload,
                         erc721_mint,
                     )
                     .await?;
@@ -135,10 +291,11 @@ pub async fn prepare_solidity_contracts(
                 let erc1155_create = || BenchERC1155::messages::create(1_000_000.into()).into();
                 runner
                     .prepare_contract(
+                        &label,
                         path,
                         "BenchERC1155",
                         erc1155_new,
-                        cli.instance_count,
+                        workload,
                         erc1155_create,
                     )
                     .await?;
@@ -148,10 +305,11 @@ pub async fn prepare_solidity_contracts(
                 let computation_odd_product = || Computation::messages::oddProduct(1000).into();
                 runner
                     .prepare_contract(
+                        &label,
                         path,
                         "Computation",
                         computation_new,
-                        cli.instance_count,
+                        workload,
                         computation_odd_product,
                     )
                     .await?;
@@ -162,10 +320,11 @@ pub async fn prepare_solidity_contracts(
                     || Computation::messages::triangleNumber(1000).into();
                 runner
                     .prepare_contract(
+                        &label,
                         path,
                         "Computation",
                         computation_new,
-                        cli.instance_count,
+                        workload,
                         computation_triangle_number,
                     )
                     .await?;
@@ -174,39 +333,21 @@ pub async fn prepare_solidity_contracts(
                 let storage_new = Storage::constructors::new();
                 let storage_read = || Storage::messages::read(bob.clone(), 10).into();
                 runner
-                    .prepare_contract(
-                        path,
-                        "Storage",
-                        storage_new,
-                        cli.instance_count,
-                        storage_read,
-                    )
+                    .prepare_contract(&label, path, "Storage", storage_new, workload, storage_read)
                     .await?;
             }
             Contract::StorageWrite => {
                 let storage_new = Storage::constructors::new();
                 let storage_read = || Storage::messages::write(bob.clone(), 10).into();
                 runner
-                    .prepare_contract(
-                        path,
-                        "Storage",
-                        storage_new,
-                        cli.instance_count,
-                        storage_read,
-                    )
+                    .prepare_contract(&label, path, "Storage", storage_new, workload, storage_read)
                     .await?;
             }
             Contract::StorageReadWrite => {
                 let storage_new = Storage::constructors::new();
                 let storage_read = || Storage::messages::readWrite(bob.clone(), 10).into();
                 runner
-                    .prepare_contract(
-                        path,
-                        "Storage",
-                        storage_new,
-                        cli.instance_count,
-                        storage_read,
-                    )
+                    .prepare_contract(&label, path, "Storage", storage_new, workload, storage_read)
                     .await?;
             }
         }
@@ -220,20 +361,16 @@ pub async fn prepare_ink_contracts(
     bob: AccountId32,
 ) -> color_eyre::Result<()> {
     use ink_contracts::*;
-    let path = "contracts/ink";
+    let path = INK_CONTRACTS_PATH;
+    let workload = Workload::from_cli(cli)?;
     for contract in &cli.contracts {
+        let label = contract.to_string();
         match contract {
             Contract::Erc20 => {
                 let erc20_new = erc20::constructors::new(1_000_000);
                 let erc20_transfer = || erc20::messages::transfer(bob.clone(), 1000).into();
                 runner
-                    .prepare_contract(
-                        path,
-                        "erc20",
-                        erc20_new,
-                        cli.instance_count,
-                        &erc20_transfer,
-                    )
+                    .prepare_contract(&label, path, "erc20", erc20_new, workload, &erc20_transfer)
                     .await?;
             }
             Contract::Flipper => {
@@ -241,10 +378,11 @@ pub async fn prepare_ink_contracts(
                 let flipper_flip = || flipper::messages::flip().into();
                 runner
                     .prepare_contract(
+                        &label,
                         path,
                         "flipper",
                         flipper_new,
-                        cli.instance_count,
+                        workload,
                         &flipper_flip,
                     )
                     .await?;
@@ -254,10 +392,11 @@ pub async fn prepare_ink_contracts(
                 let incrementer_increment = || incrementer::messages::inc(1).into();
                 runner
                     .prepare_contract(
+                        &label,
                         path,
                         "incrementer",
                         incrementer_new,
-                        cli.instance_count,
+                        workload,
                         incrementer_increment,
                     )
                     .await?;
@@ -271,7 +410,7 @@ pub async fn prepare_ink_contracts(
                     mint.into()
                 };
                 runner
-                    .prepare_contract(path, "erc721", erc721_new, cli.instance_count, erc721_mint)
+                    .prepare_contract(&label, path, "erc721", erc721_new, workload, erc721_mint)
                     .await?;
             }
             Contract::Erc1155 => {
@@ -279,10 +418,11 @@ pub async fn prepare_ink_contracts(
                 let erc1155_create = || erc1155::messages::create(1_000_000).into();
                 runner
                     .prepare_contract(
+                        &label,
                         path,
                         "erc1155",
                         erc1155_new,
-                        cli.instance_count,
+                        workload,
                         erc1155_create,
                     )
                     .await?;
@@ -292,10 +432,11 @@ pub async fn prepare_ink_contracts(
                 let computation_odd_product = || computation::messages::odd_product(1000).into();
                 runner
                     .prepare_contract(
+                        &label,
                         path,
                         "computation",
                         computation_new,
-                        cli.instance_count,
+                        workload,
                         computation_odd_product,
                     )
                     .await?;
@@ -306,10 +447,11 @@ pub async fn prepare_ink_contracts(
                     || computation::messages::triangle_number(1000).into();
                 runner
                     .prepare_contract(
+                        &label,
                         path,
                         "computation",
                         computation_new,
-                        cli.instance_count,
+                        workload,
                         computation_triangle_number,
                     )
                     .await?;
@@ -318,39 +460,21 @@ pub async fn prepare_ink_contracts(
                 let storage_new = storage::constructors::new();
                 let storage_read = || storage::messages::read(bob.clone(), 10).into();
                 runner
-                    .prepare_contract(
-                        path,
-                        "storage",
-                        storage_new,
-                        cli.instance_count,
-                        storage_read,
-                    )
+                    .prepare_contract(&label, path, "storage", storage_new, workload, storage_read)
                     .await?;
             }
             Contract::StorageWrite => {
                 let storage_new = storage::constructors::new();
                 let storage_read = || storage::messages::write(bob.clone(), 10).into();
                 runner
-                    .prepare_contract(
-                        path,
-                        "storage",
-                        storage_new,
-                        cli.instance_count,
-                        storage_read,
-                    )
+                    .prepare_contract(&label, path, "storage", storage_new, workload, storage_read)
                     .await?;
             }
             Contract::StorageReadWrite => {
                 let storage_new = storage::constructors::new();
                 let storage_read = || storage::messages::read_write(bob.clone(), 10).into();
                 runner
-                    .prepare_contract(
-                        path,
-                        "storage",
-                        storage_new,
-                        cli.instance_count,
-                        storage_read,
-                    )
+                    .prepare_contract(&label, path, "storage", storage_new, workload, storage_read)
                     .await?;
             }
         }
@@ -1,29 +1,134 @@
 use self::xts::api;
 
 use super::*;
-use crate::BlockInfo;
-use codec::Encode;
-use color_eyre::eyre;
-use futures::TryStream;
+use crate::{
+    finality::FinalizedBlocks,
+    outcome::{self, CallOutcome, RevertReason},
+    profile::{self, BlockLimits, MessageProfile, WeightProfile},
+    rate::Rate,
+    scenario::Workload,
+    schedule::Schedule,
+    stats::{BlockDetails, IncludedCall, PendingCalls, StorageDeposit, SubmittedCall},
+    value::CallValue,
+    BlockInfo, Cli, Finality, GasEstimate,
+};
+use codec::{Decode as _, Encode};
+use color_eyre::{eyre, Section as _};
+use futures::{future, StreamExt as _, TryStream, TryStreamExt as _};
+use pallet_contracts_primitives::StorageDeposit as DryRunStorageDeposit;
 use sp_runtime::traits::{BlakeTwo256, Hash as _};
-use std::time::{SystemTime, UNIX_EPOCH};
-use subxt::{backend::rpc::RpcClient, OnlineClient, PolkadotConfig as DefaultConfig};
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use subxt::{
+    backend::rpc::RpcClient,
+    blocks::{Block, ExtrinsicDetails},
+    tx::Payload,
+    utils::MultiAddress,
+    OnlineClient, PolkadotConfig as DefaultConfig,
+};
 
 use xts::api::{
-    contracts::calls::types::Call, contracts::events::Instantiated, system::events::ExtrinsicFailed,
+    contracts::calls::types::{Call, Instantiate, InstantiateWithCode},
+    contracts::events::{
+        Instantiated, StorageDepositTransferredAndHeld, StorageDepositTransferredAndReleased,
+    },
+    system::events::ExtrinsicFailed,
 };
 
-pub const DEFAULT_STORAGE_DEPOSIT_LIMIT: Option<Balance> = None;
+/// The dry runs are not limited, so that they report the storage deposit required even above
+/// `--storage-deposit-limit`.
+const DRY_RUN_STORAGE_DEPOSIT_LIMIT: Option<Balance> = None;
+
+/// The number of accounts funded in a single batch of transfers.
+const TRANSFER_BATCH_SIZE: usize = 100;
+
+/// The maximum number of reverted calls per block replayed to decode the reason of the revert.
+const MAX_REVERT_REASONS: usize = 10;
 
 pub struct BenchRunner {
     url: String,
     api: ContractsApi,
     signer: Signer,
-    calls: Vec<(String, Vec<RunnerCall>)>,
+    /// The accounts the calls are spread across, the `signer` if there are none.
+    call_signers: Vec<Signer>,
+    contracts: Vec<PreparedContract>,
+    compiler_versions: Vec<String>,
+    finality: Finality,
+    gas_strategy: GasStrategy,
+    /// Upload the code of each contract once, and instantiate all instances from its hash.
+    upload_once: bool,
+    /// Benchmark the instantiation of the contracts rather than calls to them, see
+    /// `--deployments`.
+    measure_deployments: bool,
+    deployments: Vec<PreparedDeployment>,
+    /// The storage deposit limit of the calls and instantiations, `None` for no limit.
+    storage_deposit_limit: Option<Balance>,
+    /// The weight of the messages of the prepared contracts, from their dry runs.
+    message_profiles: Vec<MessageProfile>,
+}
+
+/// How the gas limits of the calls are determined, see `--gas-estimate`.
+#[derive(Debug, Clone, Copy)]
+pub enum GasStrategy {
+    /// Dry run the call to each instance while preparing a contract, and call all instances
+    /// with the highest gas required plus `margin` percent.
+    Once { margin: u64 },
+    /// Dry run every call before submitting it, and call with the gas required plus `margin`
+    /// percent.
+    PerCall { margin: u64 },
+    /// Call with a fixed gas limit.
+    Fixed(sp_weights::Weight),
+}
+
+impl GasStrategy {
+    pub fn from_cli(cli: &Cli) -> color_eyre::Result<Self> {
+        Ok(match cli.gas_estimate {
+            GasEstimate::Once => Self::Once {
+                margin: cli.gas_margin,
+            },
+            GasEstimate::PerCall => Self::PerCall {
+                margin: cli.gas_margin,
+            },
+            GasEstimate::Fixed => {
+                let gas_limit = cli
+                    .gas_limit
+                    .ok_or_else(|| eyre::eyre!("Missing the fixed gas limit"))
+                    .suggestion("Provide --gas-limit")?;
+                Self::Fixed(sp_weights::Weight::from_parts(
+                    gas_limit.ref_time,
+                    gas_limit.proof_size,
+                ))
+            }
+        })
+    }
+}
+
+/// The gas `required` by a dry run, plus `margin` percent to avoid "not enough gas" errors.
+fn with_margin(
+    required: sp_weights::Weight,
+    margin: u64,
+) -> color_eyre::Result<sp_weights::Weight> {
+    margin
+        .checked_add(100)
+        .and_then(|percent| required.checked_mul(percent))
+        .map(|gas_limit| gas_limit / 100)
+        .ok_or_else(|| eyre::eyre!("Gas limit overflow with --gas-margin {margin}"))
+        .suggestion("Use a lower --gas-margin")
 }
 
 impl BenchRunner {
-    pub async fn new(signer: Signer, url: &str) -> color_eyre::Result<Self> {
+    pub async fn new(
+        signer: Signer,
+        url: &str,
+        finality: Finality,
+        gas_strategy: GasStrategy,
+        upload_once: bool,
+        measure_deployments: bool,
+        storage_deposit_limit: Option<Balance>,
+    ) -> color_eyre::Result<Self> {
         let client = RpcClient::from_url(url).await?;
 
         let api = ContractsApi::new(client).await?;
@@ -32,43 +137,66 @@ impl BenchRunner {
             url: url.to_string(),
             api,
             signer,
-            calls: Vec::new(),
+            call_signers: Vec::new(),
+            contracts: Vec::new(),
+            compiler_versions: Vec::new(),
+            finality,
+            gas_strategy,
+            upload_once,
+            measure_deployments,
+            deployments: Vec::new(),
+            storage_deposit_limit,
+            message_profiles: Vec::new(),
         };
         Ok(runner)
     }
 
-    /// Upload and instantiate instances of contract, and build calls for benchmarking
+    /// Upload and instantiate instances of contract, and build calls for benchmarking. The calls
+    /// are reported under the given `label`. With `--deployments`, the instances are only
+    /// instantiated by [`Self::run_deployments`].
     pub async fn prepare_contract<C, F>(
         &mut self,
+        label: &str,
         path: &str,
         name: &str,
         constructor: C,
-        instance_count: u32,
+        workload: Workload,
         mut create_message: F,
     ) -> color_eyre::Result<()>
     where
-        C: InkConstructor,
+        C: ConstructorCallData,
         F: FnMut() -> EncodedMessage,
     {
-        print!("Preparing {name}...");
+        eprint!("Preparing {name}...");
 
-        let root = std::env::var("CARGO_MANIFEST_DIR")?;
-        let contract_path = format!("{path}/{name}.contract");
-        let metadata_path: std::path::PathBuf = [&root, &contract_path].iter().collect();
-        let reader = std::fs::File::open(metadata_path)?;
-        let contract: contract_metadata::ContractMetadata = serde_json::from_reader(reader)?;
+        let contract = load_contract(path, name)?;
+        let compiler_version = contract.source.compiler.to_string();
+        if !self.compiler_versions.contains(&compiler_version) {
+            self.compiler_versions.push(compiler_version);
+        }
         let code = contract
             .source
             .wasm
             .ok_or_else(|| eyre::eyre!("contract bundle missing source Wasm"))?;
 
-        println!("{}KiB", code.0.len() / 1024);
+        eprintln!("{}KiB", code.0.len() / 1024);
 
-        let contract_accounts = self
-            .exec_instantiate(0, code.0, &constructor, instance_count)
+        let deployment = self
+            .prepare_deployment(
+                label,
+                workload.constructor_value,
+                code.0,
+                constructor.call_data(),
+                workload.instance_count,
+            )
             .await?;
+        if self.measure_deployments {
+            self.deployments.push(deployment);
+            return Ok(());
+        }
+        let contract_accounts = self.exec_instantiate(&deployment).await?;
 
-        println!("Instantiated {} {name} contracts", contract_accounts.len());
+        eprintln!("Instantiated {} {name} contracts", contract_accounts.len());
 
         let calls = contract_accounts
             .iter()
@@ -81,59 +209,206 @@ impl BenchRunner {
             })
             .collect::<Vec<_>>();
 
-        self.calls.push((name.to_string(), calls));
+        // the calls are dry run for their gas and storage deposit, unless the gas limit is fixed
+        let dry_run = match self.gas_strategy {
+            GasStrategy::Fixed(_) => None,
+            _ => Some(self.dry_run_calls(label, &calls, workload.value).await?),
+        };
+        if let Some(message) = &dry_run {
+            eprintln!(
+                "Storage deposit of {name} calls: {}",
+                message.storage_deposit
+            );
+            self.check_storage_deposit_limit(message.storage_deposit)
+                .with_note(|| format!("Dry running the {name} calls"))?;
+        }
+        let gas_limit = match (self.gas_strategy, &dry_run) {
+            (GasStrategy::Once { margin }, Some(message)) => {
+                let gas_required = sp_weights::Weight::from_parts(
+                    message.gas_required.ref_time,
+                    message.gas_required.proof_size,
+                );
+                let gas_limit = with_margin(gas_required, margin)?;
+                eprintln!(
+                    "Estimated gas limit of {name} calls: ref_time {}, proof_size {}",
+                    gas_limit.ref_time(),
+                    gas_limit.proof_size()
+                );
+                Some(gas_limit)
+            }
+            (GasStrategy::Fixed(gas_limit), _) => Some(gas_limit),
+            _ => None,
+        };
+        self.message_profiles.extend(dry_run);
+
+        self.contracts.push(PreparedContract {
+            label: label.to_string(),
+            call_count: workload.call_count,
+            calls,
+            value: workload.value,
+            gas_limit,
+        });
 
         Ok(())
     }
 
-    async fn exec_instantiate<C: InkConstructor>(
+    /// Fund the given accounts with `value` from the signer, and spread the calls across them.
+    pub async fn fund_call_signers(
         &mut self,
+        signers: Vec<Signer>,
         value: Balance,
-        code: Vec<u8>,
-        constructor: &C,
-        count: u32,
-    ) -> color_eyre::Result<Vec<AccountId>> {
-        let mut data = C::SELECTOR.to_vec();
-        <C as Encode>::encode_to(constructor, &mut data);
+    ) -> color_eyre::Result<()> {
+        eprintln!("Funding {} signers...", signers.len());
+        let accounts = signers
+            .iter()
+            .map(|signer| subxt::tx::Signer::account_id(signer).clone())
+            .collect::<Vec<_>>();
+        for accounts in accounts.chunks(TRANSFER_BATCH_SIZE) {
+            self.api
+                .transfer_batch(accounts, value, &self.signer)
+                .await?;
+        }
+        self.call_signers = signers;
+        Ok(())
+    }
+
+    /// The signer of the call with the given index.
+    fn call_signer(&self, call: usize) -> &Signer {
+        if self.call_signers.is_empty() {
+            &self.signer
+        } else {
+            &self.call_signers[call % self.call_signers.len()]
+        }
+    }
+
+    /// The compiler(s) of the prepared contracts, as found in their metadata.
+    pub fn compiler_version(&self) -> Option<String> {
+        (!self.compiler_versions.is_empty()).then(|| self.compiler_versions.join("+"))
+    }
 
-        // a value to append to a contract's custom section to make the code unique
+    /// Upload the code with `--upload-once`, and dry run the instantiation of a contract to
+    /// calculate its gas limit.
+    async fn prepare_deployment(
+        &self,
+        label: &str,
+        value: CallValue,
+        code: Vec<u8>,
+        data: Vec<u8>,
+        instance_count: u32,
+    ) -> color_eyre::Result<PreparedDeployment> {
+        // a value to append to a contract's custom section to make the code unique, or with
+        // `--upload-once` to salt the instances of the same code
         let unique_code_salt = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
 
+        let code_hash = if self.upload_once {
+            let code_hash = self
+                .api
+                .upload_code(code.clone(), self.storage_deposit_limit, &self.signer)
+                .await?;
+            eprintln!("Uploaded code {code_hash:?}");
+            Some(code_hash)
+        } else {
+            None
+        };
+
         // dry run the instantiate to calculate the gas limit
         let gas_limit = {
-            let code = append_unique_name_section(&code, unique_code_salt)?;
+            let (code, salt) = match code_hash {
+                Some(code_hash) => (
+                    xts::Code::Existing(code_hash),
+                    unique_code_salt.to_le_bytes().to_vec(),
+                ),
+                None => (
+                    xts::Code::Upload(append_unique_name_section(&code, unique_code_salt)?),
+                    Vec::new(),
+                ),
+            };
             let dry_run = self
                 .api
-                .instantiate_with_code_dry_run(
-                    value,
-                    DEFAULT_STORAGE_DEPOSIT_LIMIT,
+                .instantiate_dry_run(
+                    value.for_call(0),
+                    DRY_RUN_STORAGE_DEPOSIT_LIMIT,
                     code,
                     data.clone(),
-                    Vec::new(),
+                    salt,
                     &self.signer,
                 )
-                .await;
+                .await?;
+            let storage_deposit = storage_deposit_of(&dry_run.storage_deposit);
+            eprintln!("Storage deposit of instantiating {label}: {storage_deposit}");
+            self.check_storage_deposit_limit(storage_deposit)
+                .with_note(|| format!("Dry running the instantiation of {label}"))?;
             dry_run.gas_required
         };
 
-        let mut block_sub = self.api.client.blocks().subscribe_best().await?;
-
-        let mut accounts = Vec::new();
-        for i in unique_code_salt..unique_code_salt + count as u128 {
-            let code = append_unique_name_section(&code, i)?;
-            let salt = Vec::new();
+        Ok(PreparedDeployment {
+            label: label.to_string(),
+            value,
+            code,
+            code_hash,
+            data,
+            gas_limit,
+            unique_code_salt,
+            instance_count,
+        })
+    }
 
-            self.api
-                .instantiate_with_code(
+    /// Sign the instantiation of the instance with the given index by `signer`, with its next
+    /// nonce.
+    async fn sign_instantiate(
+        &self,
+        deployment: &PreparedDeployment,
+        instance: u32,
+        signer: &Signer,
+    ) -> color_eyre::Result<Vec<u8>> {
+        let i = deployment.unique_code_salt + u128::from(instance);
+        let value = deployment.value.for_call(instance as usize);
+        let tx = match deployment.code_hash {
+            Some(code_hash) => {
+                let salt = i.to_le_bytes().to_vec();
+                let payload = ContractsApi::instantiate_payload(
+                    value,
+                    deployment.gas_limit.into(),
+                    self.storage_deposit_limit,
+                    code_hash,
+                    deployment.data.clone(),
+                    salt,
+                );
+                self.api.sign_call(payload, signer).await?
+            }
+            None => {
+                let code = append_unique_name_section(&deployment.code, i)?;
+                let salt = Vec::new();
+                let payload = ContractsApi::instantiate_with_code_payload(
                     value,
-                    gas_limit.into(),
-                    DEFAULT_STORAGE_DEPOSIT_LIMIT,
+                    deployment.gas_limit.into(),
+                    self.storage_deposit_limit,
                     code,
-                    data.clone(),
+                    deployment.data.clone(),
                     salt,
-                    &mut self.signer,
-                )
+                );
+                self.api.sign_call(payload, signer).await?
+            }
+        };
+        Ok(tx.into_encoded())
+    }
+
+    async fn exec_instantiate(
+        &self,
+        deployment: &PreparedDeployment,
+    ) -> color_eyre::Result<Vec<AccountId>> {
+        let count = deployment.instance_count;
+        let mut block_sub = match self.finality {
+            Finality::Best => self.api.client.blocks().subscribe_best().await?,
+            Finality::Finalized => self.api.client.blocks().subscribe_finalized().await?,
+        };
+
+        let mut accounts = Vec::new();
+        for instance in 0..count {
+            let tx = self
+                .sign_instantiate(deployment, instance, &self.signer)
                 .await?;
+            self.api.submit_extrinsic(&tx).await?;
         }
 
         while let Some(Ok(block)) = block_sub.next().await {
@@ -163,22 +438,80 @@ impl BenchRunner {
         ))
     }
 
+    /// The timestamp of the block, and the hashes of its contract calls, or of its
+    /// instantiations with `deployments`, with the storage deposit they charged and refunded and
+    /// how they ended.
     async fn get_block_details(
-        client: OnlineClient<DefaultConfig>,
+        &self,
         block_hash: sp_core::H256,
-    ) -> color_eyre::Result<(u64, Vec<sp_core::H256>)> {
+        deployments: bool,
+    ) -> color_eyre::Result<BlockDetails> {
+        let client = &self.api.client;
         let block = client.blocks().at(block_hash).await?;
-        let mut tx_hashes = Vec::new();
+        let mut calls = Vec::new();
         let extrinsics_details = block
             .extrinsics()
             .await?
             .iter()
             .collect::<Result<Vec<_>, _>>()?;
 
+        // the events of the block are fetched once, rather than for each extrinsic
+        let mut storage_deposits: HashMap<u32, StorageDeposit> = HashMap::new();
+        let mut outcomes: HashMap<u32, CallOutcome> = HashMap::new();
+        for event in block.events().await?.iter() {
+            let event = event?;
+            let subxt::events::Phase::ApplyExtrinsic(index) = event.phase() else {
+                continue;
+            };
+            if let Some(held) = event.as_event::<StorageDepositTransferredAndHeld>()? {
+                storage_deposits.entry(index).or_default().charged += held.amount;
+            } else if let Some(released) =
+                event.as_event::<StorageDepositTransferredAndReleased>()?
+            {
+                storage_deposits.entry(index).or_default().refunded += released.amount;
+            } else if event.as_event::<ExtrinsicFailed>()?.is_some() {
+                let dispatch_error = subxt::error::DispatchError::decode_from(
+                    event.field_bytes(),
+                    client.metadata(),
+                )?;
+                outcomes.insert(index, dispatch_outcome(dispatch_error));
+            }
+        }
+
+        let mut revert_reasons = 0;
         for extrinsic_detail in extrinsics_details {
-            if let Some(Call { .. }) = extrinsic_detail.as_extrinsic::<Call>()? {
-                tx_hashes.push(BlakeTwo256::hash_of(&extrinsic_detail.bytes()));
+            let benchmarked = if deployments {
+                extrinsic_detail.as_extrinsic::<Instantiate>()?.is_some()
+                    || extrinsic_detail
+                        .as_extrinsic::<InstantiateWithCode>()?
+                        .is_some()
+            } else {
+                extrinsic_detail.as_extrinsic::<Call>()?.is_some()
+            };
+            if !benchmarked {
+                continue;
+            }
+            let mut outcome = outcomes
+                .remove(&extrinsic_detail.index())
+                .unwrap_or(CallOutcome::Success);
+            if outcome == CallOutcome::Reverted(None) && revert_reasons < MAX_REVERT_REASONS {
+                revert_reasons += 1;
+                if let Some(call) = extrinsic_detail.as_extrinsic::<Call>()? {
+                    let reason = self
+                        .replay_reverted_call(&block, &extrinsic_detail, call)
+                        .await
+                        .map(RevertReason::Replayed);
+                    outcome = CallOutcome::Reverted(reason);
+                }
             }
+            calls.push(IncludedCall {
+                hash: BlakeTwo256::hash_of(&extrinsic_detail.bytes()),
+                storage_deposit: storage_deposits
+                    .get(&extrinsic_detail.index())
+                    .copied()
+                    .unwrap_or_default(),
+                outcome,
+            });
         }
         let storage_timestamp_storage_addr = api::storage().timestamp().now();
         let time_stamp = client
@@ -187,76 +520,378 @@ impl BenchRunner {
             .fetch(&storage_timestamp_storage_addr)
             .await?
             .unwrap();
-        Ok((time_stamp, tx_hashes))
+        Ok(BlockDetails { time_stamp, calls })
+    }
+
+    /// The reason a call reverted with, by replaying it on the state of the parent of its block,
+    /// `None` if the replay didn't revert as well, or failed.
+    async fn replay_reverted_call(
+        &self,
+        block: &Block<DefaultConfig, OnlineClient<DefaultConfig>>,
+        extrinsic_detail: &ExtrinsicDetails<DefaultConfig, OnlineClient<DefaultConfig>>,
+        call: Call,
+    ) -> Option<String> {
+        let mut address = extrinsic_detail.address_bytes()?;
+        let Ok(MultiAddress::Id(origin)) = MultiAddress::<AccountId, ()>::decode(&mut address)
+        else {
+            return None;
+        };
+        let MultiAddress::Id(contract) = call.dest else {
+            return None;
+        };
+        let dry_run = self
+            .api
+            .call_dry_run_at(
+                origin,
+                contract,
+                call.value,
+                call.storage_deposit_limit.map(|limit| limit.0),
+                call.data,
+                Some(block.header().parent_hash),
+            )
+            .await;
+        match dry_run {
+            Ok(dry_run) => match dry_run.result {
+                Ok(result) if result.did_revert() => Some(outcome::revert_reason(&result.data)),
+                _ => None,
+            },
+            Err(err) => {
+                tracing::debug!("Error replaying reverted call: {err:?}");
+                None
+            }
+        }
+    }
+
+    /// The highest gas consumed and required, and storage deposit, of the dry runs of the calls
+    /// reported under `label`, by the signer of the first call.
+    async fn dry_run_calls(
+        &self,
+        label: &str,
+        calls: &[RunnerCall],
+        value: CallValue,
+    ) -> color_eyre::Result<MessageProfile> {
+        let mut max_gas_consumed = sp_weights::Weight::zero();
+        let mut max_gas_required = sp_weights::Weight::zero();
+        let mut max_storage_deposit = StorageDeposit::default();
+        for (instance, call) in calls.iter().enumerate() {
+            let dry_run = self
+                .api
+                .call_dry_run(
+                    call.contract_account.clone(),
+                    value.for_call(instance),
+                    DRY_RUN_STORAGE_DEPOSIT_LIMIT,
+                    call.call_data.0.clone(),
+                    self.call_signer(0),
+                )
+                .await?;
+            max_gas_consumed = max_gas_consumed.max(dry_run.gas_consumed);
+            max_gas_required = max_gas_required.max(dry_run.gas_required);
+            let storage_deposit = storage_deposit_of(&dry_run.storage_deposit);
+            if storage_deposit.locked() > max_storage_deposit.locked() {
+                max_storage_deposit = storage_deposit;
+            }
+        }
+        Ok(MessageProfile {
+            contract: label.to_string(),
+            gas_consumed: profile_weight(max_gas_consumed),
+            gas_required: profile_weight(max_gas_required),
+            storage_deposit: max_storage_deposit,
+            max_calls_per_block: None,
+        })
+    }
+
+    /// The weight profile of the messages dry run while preparing the contracts, with the block
+    /// limits of the runtime. `None` with `--gas-estimate fixed`, which skips the dry runs.
+    pub fn weight_profile(&self) -> color_eyre::Result<Option<WeightProfile>> {
+        if self.message_profiles.is_empty() {
+            return Ok(None);
+        }
+        let block_weights = self
+            .api
+            .client
+            .constants()
+            .at(&api::constants().system().block_weights())?;
+        let normal = block_weights.per_class.normal;
+        let max_normal = normal.max_total.unwrap_or(block_weights.max_block);
+        let block_limits = BlockLimits {
+            max_normal: profile::Weight {
+                ref_time: max_normal.ref_time,
+                proof_size: max_normal.proof_size,
+            },
+            base_extrinsic: profile::Weight {
+                ref_time: normal.base_extrinsic.ref_time,
+                proof_size: normal.base_extrinsic.proof_size,
+            },
+        };
+        Ok(Some(WeightProfile::new(
+            block_limits,
+            self.message_profiles.clone(),
+        )))
+    }
+
+    /// Fail early if the storage deposit of a dry run exceeds `--storage-deposit-limit`, rather
+    /// than with every call.
+    fn check_storage_deposit_limit(
+        &self,
+        storage_deposit: StorageDeposit,
+    ) -> color_eyre::Result<()> {
+        match self.storage_deposit_limit {
+            Some(limit) if storage_deposit.locked() > limit as i128 => Err(eyre::eyre!(
+                "Storage deposit of {} exceeds --storage-deposit-limit {limit}",
+                storage_deposit.locked()
+            ))
+            .suggestion("Raise --storage-deposit-limit, or leave it unlimited"),
+            _ => Ok(()),
+        }
+    }
+
+    /// The signer, value and gas limit of the call with the given index to an instance of a
+    /// contract, dry running the call with `--gas-estimate per-call`.
+    async fn schedule_call(
+        &self,
+        call: usize,
+        contract: usize,
+        instance: usize,
+    ) -> color_eyre::Result<ScheduledCall<'_>> {
+        let contract = &self.contracts[contract];
+        let contract_call = &contract.calls[instance];
+        let signer = self.call_signer(call);
+        let value = contract.value.for_call(call);
+        let gas_limit = match self.gas_strategy {
+            GasStrategy::PerCall { margin } => {
+                let dry_run = self
+                    .api
+                    .call_dry_run(
+                        contract_call.contract_account.clone(),
+                        value,
+                        DRY_RUN_STORAGE_DEPOSIT_LIMIT,
+                        contract_call.call_data.0.clone(),
+                        signer,
+                    )
+                    .await?;
+                with_margin(dry_run.gas_required, margin)?
+            }
+            _ => contract.gas_limit.expect("Gas limit set when preparing"),
+        };
+        Ok(ScheduledCall {
+            contract,
+            call: contract_call,
+            signer,
+            value,
+            gas_limit,
+            storage_deposit_limit: self.storage_deposit_limit,
+        })
+    }
+
+    /// Sign all calls of the `schedule` up front for `--pre-sign`, after dry running them with
+    /// `--gas-estimate per-call`.
+    async fn pre_sign_calls(
+        &self,
+        schedule: Schedule,
+        max_in_flight: NonZeroUsize,
+    ) -> color_eyre::Result<Vec<(&PreparedContract, Vec<u8>)>> {
+        eprintln!("Pre-signing calls...");
+        let calls = futures::stream::iter(schedule.enumerate())
+            .map(|(call, (contract, instance))| self.schedule_call(call, contract, instance))
+            .buffered(max_in_flight.get())
+            .try_collect::<Vec<_>>()
+            .await?;
+        let payloads = calls
+            .iter()
+            .map(|call| (call.payload(), call.signer))
+            .collect();
+        let signed_calls = self.api.sign_calls(payloads).await?;
+        eprintln!("Pre-signed {} calls", signed_calls.len());
+        Ok(calls
+            .into_iter()
+            .map(|call| call.contract)
+            .zip(signed_calls)
+            .collect())
     }
 
-    /// Call each contract instance `call_count` times. Wait for all txs to be included in a block
-    /// before returning.
+    /// The label, number of instances and call count of each prepared contract, to schedule the
+    /// calls with.
+    pub fn workloads(&self) -> impl Iterator<Item = (&str, usize, Option<u32>)> {
+        self.contracts.iter().map(|contract| {
+            (
+                contract.label.as_str(),
+                contract.calls.len(),
+                contract.call_count,
+            )
+        })
+    }
+
+    /// Call the contract instances in the order of the `schedule`, at the given `rate` or as fast
+    /// as possible, with up to `max_in_flight` calls being submitted at once. With `pre_sign`, all calls are signed before any is submitted.
+    /// The returned stream yields the blocks while the calls are submitted, and completes once
+    /// all of them are included in a block.
     pub async fn run(
         &mut self,
-        call_count: u32,
+        finalized_blocks: FinalizedBlocks,
+        schedule: Schedule,
+        rate: Option<Rate>,
+        max_in_flight: NonZeroUsize,
+        pre_sign: bool,
     ) -> color_eyre::Result<impl TryStream<Ok = BlockInfo, Error = color_eyre::Report> + '_> {
+        if self.contracts.is_empty() {
+            return Err(eyre::eyre!("No prepared contracts for benchmarking."));
+        }
+
+        let this = &*self;
+        let signed_calls = if pre_sign {
+            let calls = this.pre_sign_calls(schedule, max_in_flight).await?;
+            crate::rate::paced(calls.into_iter(), rate)
+                .map(Ok::<_, color_eyre::Report>)
+                .left_stream()
+        } else {
+            crate::rate::paced(schedule.enumerate(), rate)
+                .map(move |(call, (contract, instance))| {
+                    this.schedule_call(call, contract, instance)
+                })
+                .buffered(max_in_flight.get())
+                // the nonces are assigned in the order of the calls, even though up to
+                // `max_in_flight` of them are dry run and submitted concurrently
+                .and_then(move |call| async move {
+                    let tx = this.api.sign_call(call.payload(), call.signer).await?;
+                    Ok((call.contract, tx.into_encoded()))
+                })
+                .right_stream()
+        };
+
         let block_stats = blockstats::subscribe_stats(&self.url).await?;
+        let pending = PendingCalls::default();
+        let submitted_calls = pending.clone();
+        let submit_calls = async move {
+            let mut submitted_count = 0;
+            signed_calls
+                .map_ok(|(contract, tx)| async move {
+                    let submitted = Instant::now();
+                    let tx_hash = this.api.submit_extrinsic(&tx).await?;
+                    Ok((contract, tx_hash, submitted))
+                })
+                .try_buffer_unordered(max_in_flight.get())
+                .try_for_each(|(contract, tx_hash, submitted)| {
+                    submitted_calls.insert(
+                        tx_hash,
+                        SubmittedCall {
+                            contract: contract.label.clone(),
+                            submitted,
+                        },
+                    );
+                    submitted_count += 1;
+                    crate::metrics::tx_submitted();
+                    future::ready(Ok(()))
+                })
+                .await?;
+            submitted_calls.finish();
+            eprintln!("Submitted {submitted_count} total contract calls");
+            Ok::<_, color_eyre::Report>(())
+        };
 
-        let mut tx_hashes = Vec::new();
-        let max_instance_count = self
-            .calls
-            .iter()
-            .map(|(_, calls)| calls.len())
-            .max()
-            .ok_or_else(|| eyre::eyre!("No prepared contracts for benchmarking."))?;
-
-        for _ in 0..call_count {
-            for i in 0..max_instance_count {
-                for (_name, contract_calls) in &self.calls {
-                    if let Some(contract_call) = contract_calls.get(i as usize) {
-                        // dry run the call to calculate the gas limit
-                        let mut gas_limit = {
-                            let dry_run = self
-                                .api
-                                .call_dry_run(
-                                    contract_call.contract_account.clone(),
-                                    0,
-                                    DEFAULT_STORAGE_DEPOSIT_LIMIT,
-                                    contract_call.call_data.0.clone(),
-                                    &self.signer,
-                                )
-                                .await?;
-                            dry_run.gas_required
-                        };
-
-                        // extra 5% of gas limit
-                        // due to "not enough gas" rpc errors
-                        gas_limit = gas_limit.checked_mul(105).expect("Gas limit overflow") / 100;
-
-                        let tx_hash = self
-                            .api
-                            .call(
-                                contract_call.contract_account.clone(),
-                                0,
-                                gas_limit.into(),
-                                DEFAULT_STORAGE_DEPOSIT_LIMIT,
-                                contract_call.call_data.0.clone(),
-                                &self.signer,
-                            )
-                            .await?;
-                        tx_hashes.push(tx_hash)
-                    }
-                }
-            }
-        }
+        let wait_for_txs = crate::collect_block_stats(
+            block_stats,
+            pending,
+            this.finality,
+            finalized_blocks,
+            move |hash| this.get_block_details(hash, false),
+        );
 
-        println!("Submitted {} total contract calls", tx_hashes.len());
+        Ok(crate::stats::submit_while_collecting(
+            submit_calls,
+            wait_for_txs,
+        ))
+    }
 
-        let remaining_hashes: std::collections::HashSet<Hash> = tx_hashes.iter().cloned().collect();
+    /// Instantiate the instances of the contracts prepared with `--deployments`, at the given
+    /// `rate` or as fast as possible, with up to `max_in_flight` instantiations being submitted at
+    /// once. They are spread across the `--signers` like calls. The returned stream yields the
+    /// blocks while the instantiations are submitted, and completes once all of them are included
+    /// in a block.
+    pub async fn run_deployments(
+        &mut self,
+        finalized_blocks: FinalizedBlocks,
+        rate: Option<Rate>,
+        max_in_flight: NonZeroUsize,
+    ) -> color_eyre::Result<impl TryStream<Ok = BlockInfo, Error = color_eyre::Report> + '_> {
+        if self.deployments.is_empty() {
+            return Err(eyre::eyre!("No prepared contracts for benchmarking."));
+        }
 
-        let wait_for_txs = crate::collect_block_stats(block_stats, remaining_hashes, |hash| {
-            let client = self.api.client.clone();
-            Self::get_block_details(client, hash)
+        let this = &*self;
+        let instances = this.deployments.iter().flat_map(|deployment| {
+            (0..deployment.instance_count).map(move |instance| (deployment, instance))
         });
+        // the nonces are assigned in the order of the instantiations, even though up to
+        // `max_in_flight` of them are submitted concurrently
+        let signed_instantiations = crate::rate::paced(instances.enumerate(), rate).then(
+            move |(index, (deployment, instance))| async move {
+                let signer = this.call_signer(index);
+                let tx = this.sign_instantiate(deployment, instance, signer).await?;
+                Ok::<_, color_eyre::Report>((deployment, tx))
+            },
+        );
 
-        Ok(wait_for_txs)
+        let block_stats = blockstats::subscribe_stats(&self.url).await?;
+        let pending = PendingCalls::default();
+        let submitted_calls = pending.clone();
+        let submit_calls = async move {
+            let mut submitted_count = 0;
+            signed_instantiations
+                .map_ok(|(deployment, tx)| async move {
+                    let submitted = Instant::now();
+                    let tx_hash = this.api.submit_extrinsic(&tx).await?;
+                    Ok((deployment, tx_hash, submitted))
+                })
+                .try_buffer_unordered(max_in_flight.get())
+                .try_for_each(|(deployment, tx_hash, submitted)| {
+                    submitted_calls.insert(
+                        tx_hash,
+                        SubmittedCall {
+                            contract: deployment.label.clone(),
+                            submitted,
+                        },
+                    );
+                    submitted_count += 1;
+                    crate::metrics::tx_submitted();
+                    future::ready(Ok(()))
+                })
+                .await?;
+            submitted_calls.finish();
+            eprintln!("Submitted {submitted_count} total instantiations");
+            Ok::<_, color_eyre::Report>(())
+        };
+
+        let wait_for_txs = crate::collect_block_stats(
+            block_stats,
+            pending,
+            this.finality,
+            finalized_blocks,
+            move |hash| this.get_block_details(hash, true),
+        );
+
+        Ok(crate::stats::submit_while_collecting(
+            submit_calls,
+            wait_for_txs,
+        ))
+    }
+}
+
+/// How a call ended which failed with the given dispatch error: `ContractReverted` if the contract
+/// reverted, and any other error outside of the contract.
+fn dispatch_outcome(dispatch_error: subxt::error::DispatchError) -> CallOutcome {
+    if let subxt::error::DispatchError::Module(module_error) = &dispatch_error {
+        if let Ok(details) = module_error.details() {
+            if details.pallet.name() == "Contracts" && details.variant.name == "ContractReverted" {
+                return CallOutcome::Reverted(None);
+            }
+            return CallOutcome::DispatchError(format!(
+                "{}::{}",
+                details.pallet.name(),
+                details.variant.name
+            ));
+        }
     }
+    CallOutcome::DispatchError(format!("{dispatch_error:?}"))
 }
 
 /// Add a custom section to make the Wasm code unique to upload many copies of the same contract.
@@ -267,6 +902,26 @@ fn append_unique_name_section(code: &[u8], instance_id: u128) -> color_eyre::Res
     Ok(code)
 }
 
+/// Load the metadata of the contract bundle `{path}/{name}.contract`, relative to the root of the
+/// repository, or of the bundle at `name` if it is the path of a `.contract` file.
+pub fn load_contract(
+    path: &str,
+    name: &str,
+) -> color_eyre::Result<contract_metadata::ContractMetadata> {
+    let metadata_path: std::path::PathBuf = if name.ends_with(".contract") {
+        name.into()
+    } else {
+        let root = std::env::var("CARGO_MANIFEST_DIR")?;
+        let contract_path = format!("{path}/{name}.contract");
+        [&root, &contract_path].iter().collect()
+    };
+    let reader = std::fs::File::open(&metadata_path)
+        .with_note(|| format!("Error reading contract bundle {}", metadata_path.display()))
+        .suggestion("Contracts which are not committed compiled, like escrow, have to be built first, e.g. with contracts/build_escrow.sh")?;
+    let contract = serde_json::from_reader(reader)?;
+    Ok(contract)
+}
+
 #[derive(Clone)]
 pub struct EncodedMessage(Vec<u8>);
 
@@ -276,6 +931,11 @@ impl EncodedMessage {
         <M as Encode>::encode_to(call, &mut call_data);
         Self(call_data)
     }
+
+    /// A message encoded elsewhere, e.g. from the contract metadata with [`super::abi::InkAbi`].
+    pub fn from_call_data(call_data: Vec<u8>) -> Self {
+        Self(call_data)
+    }
 }
 
 impl<M> From<M> for EncodedMessage
@@ -287,6 +947,99 @@ where
     }
 }
 
+/// Call data of a contract constructor, generated by [`smart_bench_macro::contract`] or encoded
+/// from the contract metadata.
+pub trait ConstructorCallData {
+    fn call_data(&self) -> Vec<u8>;
+}
+
+impl<C: InkConstructor> ConstructorCallData for C {
+    fn call_data(&self) -> Vec<u8> {
+        let mut data = C::SELECTOR.to_vec();
+        <C as Encode>::encode_to(self, &mut data);
+        data
+    }
+}
+
+impl ConstructorCallData for EncodedMessage {
+    fn call_data(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+}
+
+/// The calls to the instances of a contract.
+struct PreparedContract {
+    /// The name the calls are reported under.
+    label: String,
+    /// The number of calls to make to each instance, `None` to call them until the duration of
+    /// the run has elapsed.
+    call_count: Option<u32>,
+    calls: Vec<RunnerCall>,
+    /// The value transferred with the calls.
+    value: CallValue,
+    /// The gas limit of all calls, `None` with `--gas-estimate per-call`.
+    gas_limit: Option<sp_weights::Weight>,
+}
+
+/// The instantiation of the instances of a contract.
+struct PreparedDeployment {
+    /// The name the instantiations are reported under.
+    label: String,
+    value: CallValue,
+    /// The code of the contract, made unique for each instance unless it was uploaded once.
+    code: Vec<u8>,
+    /// The hash of the code uploaded with `--upload-once`.
+    code_hash: Option<sp_core::H256>,
+    data: Vec<u8>,
+    gas_limit: sp_weights::Weight,
+    /// The value the instances are made unique with, increased by the index of the instance.
+    unique_code_salt: u128,
+    instance_count: u32,
+}
+
+fn profile_weight(weight: sp_weights::Weight) -> profile::Weight {
+    profile::Weight {
+        ref_time: weight.ref_time(),
+        proof_size: weight.proof_size(),
+    }
+}
+
+/// The storage deposit charged or refunded by a dry run.
+fn storage_deposit_of(dry_run: &DryRunStorageDeposit<Balance>) -> StorageDeposit {
+    match *dry_run {
+        DryRunStorageDeposit::Charge(charged) => StorageDeposit {
+            charged,
+            refunded: 0,
+        },
+        DryRunStorageDeposit::Refund(refunded) => StorageDeposit {
+            charged: 0,
+            refunded,
+        },
+    }
+}
+
+/// A call of the schedule, ready to be signed.
+struct ScheduledCall<'a> {
+    contract: &'a PreparedContract,
+    call: &'a RunnerCall,
+    signer: &'a Signer,
+    value: Balance,
+    gas_limit: sp_weights::Weight,
+    storage_deposit_limit: Option<Balance>,
+}
+
+impl ScheduledCall<'_> {
+    fn payload(&self) -> Payload<xts::Call> {
+        ContractsApi::call_payload(
+            self.call.contract_account.clone(),
+            self.value,
+            self.gas_limit.into(),
+            self.storage_deposit_limit,
+            self.call.call_data.0.clone(),
+        )
+    }
+}
+
 #[derive(Clone)]
 pub struct RunnerCall {
     contract_account: AccountId,
@@ -1,16 +1,20 @@
 use std::{
-    cell::RefCell,
     collections::{hash_map::Entry, HashMap},
+    sync::Mutex,
 };
 
 use super::*;
 use codec::{Decode, Encode, MaxEncodedLen};
+use color_eyre::Section as _;
 use pallet_contracts_primitives::{ContractExecResult, ContractInstantiateResult};
 use serde::{Deserialize, Serialize};
 use sp_core::{Pair, H256};
+use sp_runtime::traits::{BlakeTwo256, Hash as _};
 use subxt::{
     backend::{legacy::LegacyRpcMethods, rpc::RpcClient},
+    dynamic::Value,
     ext::scale_encode::EncodeAsType,
+    tx::{Payload, SubmittableExtrinsic},
     utils::MultiAddress,
     OnlineClient, PolkadotConfig as DefaultConfig,
 };
@@ -23,7 +27,7 @@ pub mod api {}
 pub struct ContractsApi {
     pub client: OnlineClient<DefaultConfig>,
     pub rpc: LegacyRpcMethods<DefaultConfig>,
-    nonces_cache: RefCell<HashMap<<sp_core::sr25519::Pair as sp_core::Pair>::Public, u64>>,
+    nonces_cache: Mutex<HashMap<<sp_core::sr25519::Pair as sp_core::Pair>::Public, u64>>,
 }
 
 impl ContractsApi {
@@ -38,17 +42,17 @@ impl ContractsApi {
         })
     }
 
-    /// Submit extrinsic to instantiate a contract with the given code.
-    pub async fn instantiate_with_code_dry_run(
+    /// Dry run the instantiation of a contract with the given code, or from the hash of code
+    /// uploaded before.
+    pub async fn instantiate_dry_run(
         &self,
         value: Balance,
         storage_deposit_limit: Option<Balance>,
-        code: Vec<u8>,
+        code: Code,
         data: Vec<u8>,
         salt: Vec<u8>,
         signer: &Signer,
-    ) -> ContractInstantiateResult<AccountId, Balance, EventRecord> {
-        let code = Code::Upload(code);
+    ) -> color_eyre::Result<ContractInstantiateResult<AccountId, Balance, EventRecord>> {
         let call_request = InstantiateRequest {
             origin: subxt::tx::Signer::account_id(signer).clone(),
             value,
@@ -59,26 +63,29 @@ impl ContractsApi {
             salt,
         };
         let bytes = self
-            .state_call("ContractsApi_instantiate", Encode::encode(&call_request))
+            .state_call(
+                "ContractsApi_instantiate",
+                Encode::encode(&call_request),
+                None,
+            )
             .await
-            .unwrap_or_else(|err| panic!("error on ws request `contracts_instantiate`: {err:?}"));
+            .note("Error on ws request `contracts_instantiate`")?;
+        let result =
+            Decode::decode(&mut bytes.as_ref()).note("Error decoding ContractInstantiateResult")?;
 
-        Decode::decode(&mut bytes.as_ref())
-            .unwrap_or_else(|err| panic!("decoding ContractInstantiateResult failed: {err}"))
+        Ok(result)
     }
 
-    /// Submit extrinsic to instantiate a contract with the given code.
-    pub async fn instantiate_with_code(
-        &self,
+    /// The extrinsic to instantiate a contract with the given code.
+    pub fn instantiate_with_code_payload(
         value: Balance,
         gas_limit: Weight,
         storage_deposit_limit: Option<Balance>,
         code: Vec<u8>,
         data: Vec<u8>,
         salt: Vec<u8>,
-        signer: &Signer,
-    ) -> color_eyre::Result<H256> {
-        let call = subxt::tx::Payload::new(
+    ) -> Payload<InstantiateWithCode> {
+        Payload::new(
             "Contracts",
             "instantiate_with_code",
             InstantiateWithCode {
@@ -90,17 +97,65 @@ impl ContractsApi {
                 salt,
             },
         )
+        .unvalidated()
+    }
+
+    /// The extrinsic to instantiate a contract from the hash of code uploaded before.
+    pub fn instantiate_payload(
+        value: Balance,
+        gas_limit: Weight,
+        storage_deposit_limit: Option<Balance>,
+        code_hash: H256,
+        data: Vec<u8>,
+        salt: Vec<u8>,
+    ) -> Payload<Instantiate> {
+        Payload::new(
+            "Contracts",
+            "instantiate",
+            Instantiate {
+                value,
+                gas_limit,
+                storage_deposit_limit,
+                code_hash,
+                data,
+                salt,
+            },
+        )
+        .unvalidated()
+    }
+
+    /// Upload the code of a contract to instantiate it from its hash, waiting until it is in a
+    /// block. Code which is already on chain is not stored again.
+    pub async fn upload_code(
+        &self,
+        code: Vec<u8>,
+        storage_deposit_limit: Option<Balance>,
+        signer: &Signer,
+    ) -> color_eyre::Result<H256> {
+        let code_hash = BlakeTwo256::hash(&code);
+        let call = subxt::tx::Payload::new(
+            "Contracts",
+            "upload_code",
+            UploadCode {
+                code,
+                storage_deposit_limit,
+                determinism: Determinism::Enforced,
+            },
+        )
         .unvalidated();
         let account_nonce = self.get_account_nonce(signer).await?;
 
-        let tx_hash = self
-            .client
+        self.client
             .tx()
             .create_signed_with_nonce(&call, signer, account_nonce, Default::default())?
-            .submit()
+            .submit_and_watch()
+            .await?
+            .wait_for_in_block()
+            .await?
+            .wait_for_success()
             .await?;
 
-        Ok(tx_hash)
+        Ok(code_hash)
     }
 
     /// Submit extrinsic to call a contract.
@@ -111,9 +166,31 @@ impl ContractsApi {
         storage_deposit_limit: Option<Balance>,
         input_data: Vec<u8>,
         signer: &Signer,
+    ) -> color_eyre::Result<ContractExecResult<Balance, EventRecord>> {
+        self.call_dry_run_at(
+            signer.account_id().clone(),
+            contract,
+            value,
+            storage_deposit_limit,
+            input_data,
+            None,
+        )
+        .await
+    }
+
+    /// Dry run a call of `origin` to a contract on the state of the block `at`, or of the best
+    /// block if `None`.
+    pub async fn call_dry_run_at(
+        &self,
+        origin: AccountId,
+        contract: AccountId,
+        value: Balance,
+        storage_deposit_limit: Option<Balance>,
+        input_data: Vec<u8>,
+        at: Option<H256>,
     ) -> color_eyre::Result<ContractExecResult<Balance, EventRecord>> {
         let call_request = RpcCallRequest {
-            origin: signer.account_id().clone(),
+            origin,
             dest: contract,
             value,
             gas_limit: DRY_RUN_GAS_LIMIT,
@@ -121,54 +198,105 @@ impl ContractsApi {
             input_data,
         };
         let bytes = self
-            .state_call("ContractsApi_call", Encode::encode(&call_request))
+            .state_call("ContractsApi_call", Encode::encode(&call_request), at)
             .await
-            .unwrap_or_else(|err| panic!("error on ws request `contract_call`: {err:?}"));
-        let result: ContractExecResult<Balance, EventRecord> = Decode::decode(&mut bytes.as_ref())
-            .unwrap_or_else(|err| panic!("decoding ContractExecResult failed: {err}"));
+            .note("Error on ws request `contract_call`")?;
+        let result: ContractExecResult<Balance, EventRecord> =
+            Decode::decode(&mut bytes.as_ref()).note("Error decoding ContractExecResult")?;
 
         Ok(result)
     }
 
     async fn get_account_nonce(&self, signer: &Signer) -> core::result::Result<u64, subxt::Error> {
-        let mut map = self.nonces_cache.borrow_mut();
+        let public = signer.signer().public();
+        {
+            let mut map = self.nonces_cache.lock().unwrap();
+            if let Some(nonce) = map.get_mut(&public) {
+                *nonce += 1;
+                return Ok(*nonce);
+            }
+        }
+
+        // the cache isn't locked while querying the nonce, calls may be signed concurrently
+        let best_block = self
+            .rpc
+            .chain_get_block_hash(None)
+            .await?
+            .ok_or(subxt::Error::Other("Best block not found".into()))?;
 
-        match (*map).entry(signer.signer().public()) {
+        let account_nonce = self
+            .client
+            .blocks()
+            .at(best_block)
+            .await?
+            .account_nonce(signer.account_id())
+            .await?;
+
+        let mut map = self.nonces_cache.lock().unwrap();
+        match map.entry(public) {
+            // another call of the signer queried the nonce meanwhile
             Entry::Occupied(mut o) => {
                 *o.get_mut() += 1;
                 Ok(*o.get())
             }
-            Entry::Vacant(v) => {
-                let best_block = self
-                    .rpc
-                    .chain_get_block_hash(None)
-                    .await?
-                    .ok_or(subxt::Error::Other("Best block not found".into()))?;
-
-                let account_nonce = self
-                    .client
-                    .blocks()
-                    .at(best_block)
-                    .await?
-                    .account_nonce(signer.account_id())
-                    .await?;
-                v.insert(account_nonce);
-                Ok(account_nonce)
-            }
+            Entry::Vacant(v) => Ok(*v.insert(account_nonce)),
         }
     }
 
-    /// Submit extrinsic to call a contract.
-    pub async fn call(
+    /// Sign extrinsic to call or instantiate a contract with the next nonce of the signer, to be
+    /// submitted later.
+    pub async fn sign_call<CallData: EncodeAsType>(
         &self,
+        call: Payload<CallData>,
+        signer: &Signer,
+    ) -> color_eyre::Result<SubmittableExtrinsic<DefaultConfig, OnlineClient<DefaultConfig>>> {
+        let account_nonce = self.get_account_nonce(signer).await?;
+
+        let tx = self.client.tx().create_signed_with_nonce(
+            &call,
+            signer,
+            account_nonce,
+            Default::default(),
+        )?;
+
+        Ok(tx)
+    }
+
+    /// Sign extrinsics to call contracts up front, with the next nonces of their signers in the
+    /// order of the calls. The signing is spread across the CPU cores.
+    pub async fn sign_calls(
+        &self,
+        calls: Vec<(Payload<Call>, &Signer)>,
+    ) -> color_eyre::Result<Vec<Vec<u8>>> {
+        let mut nonced_calls = Vec::with_capacity(calls.len());
+        for (call, signer) in calls {
+            let account_nonce = self.get_account_nonce(signer).await?;
+            nonced_calls.push((call, signer, account_nonce));
+        }
+
+        let client = &self.client;
+        let signed_calls =
+            crate::presign::sign_in_parallel(nonced_calls, |(call, signer, account_nonce)| {
+                let tx = client.tx().create_signed_with_nonce(
+                    &call,
+                    signer,
+                    account_nonce,
+                    Default::default(),
+                )?;
+                Ok::<_, subxt::Error>(tx.into_encoded())
+            });
+        Ok(signed_calls.into_iter().collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// The extrinsic to call a contract.
+    pub fn call_payload(
         contract: AccountId,
         value: Balance,
         gas_limit: Weight,
         storage_deposit_limit: Option<Balance>,
         data: Vec<u8>,
-        signer: &Signer,
-    ) -> color_eyre::Result<Hash> {
-        let call = subxt::tx::Payload::new(
+    ) -> Payload<Call> {
+        Payload::new(
             "Contracts",
             "call",
             Call {
@@ -179,22 +307,67 @@ impl ContractsApi {
                 data,
             },
         )
-        .unvalidated();
+        .unvalidated()
+    }
+
+    /// Submit an extrinsic signed up front, returning its hash.
+    pub async fn submit_extrinsic(&self, extrinsic: &[u8]) -> color_eyre::Result<Hash> {
+        Ok(self.rpc.author_submit_extrinsic(extrinsic).await?)
+    }
+
+    /// Transfer `value` to each of the accounts in a batch, waiting until it is in a block.
+    pub async fn transfer_batch(
+        &self,
+        accounts: &[AccountId],
+        value: Balance,
+        signer: &Signer,
+    ) -> color_eyre::Result<()> {
+        let transfers = accounts
+            .iter()
+            .map(|account| {
+                Value::unnamed_variant(
+                    "Balances",
+                    [Value::named_variant(
+                        "transfer_keep_alive",
+                        [
+                            (
+                                "dest",
+                                Value::unnamed_variant("Id", [Value::from_bytes(account.0)]),
+                            ),
+                            ("value", Value::u128(value)),
+                        ],
+                    )],
+                )
+            })
+            .collect::<Vec<_>>();
+        let call = subxt::dynamic::tx(
+            "Utility",
+            "batch_all",
+            vec![Value::unnamed_composite(transfers)],
+        );
 
         let account_nonce = self.get_account_nonce(signer).await?;
 
-        let tx_hash = self
-            .client
+        self.client
             .tx()
             .create_signed_with_nonce(&call, signer, account_nonce, Default::default())?
-            .submit()
+            .submit_and_watch()
+            .await?
+            .wait_for_in_block()
+            .await?
+            .wait_for_success()
             .await?;
 
-        Ok(tx_hash)
+        Ok(())
     }
 
-    async fn state_call(&self, function: &str, payload: Vec<u8>) -> color_eyre::Result<Vec<u8>> {
-        let val = self.rpc.state_call(function, Some(&payload), None).await?;
+    async fn state_call(
+        &self,
+        function: &str,
+        payload: Vec<u8>,
+        at: Option<H256>,
+    ) -> color_eyre::Result<Vec<u8>> {
+        let val = self.rpc.state_call(function, Some(&payload), at).await?;
         Ok(val)
     }
 }
@@ -224,6 +397,39 @@ pub struct InstantiateWithCode {
     salt: Vec<u8>,
 }
 
+/// A raw call to `pallet-contracts`'s `instantiate`.
+#[derive(Debug, Encode, Decode, EncodeAsType)]
+#[encode_as_type(trait_bounds = "", crate_path = "subxt::ext::scale_encode")]
+pub struct Instantiate {
+    #[codec(compact)]
+    value: Balance,
+    gas_limit: Weight,
+    storage_deposit_limit: Option<Balance>,
+    code_hash: H256,
+    data: Vec<u8>,
+    salt: Vec<u8>,
+}
+
+/// A raw call to `pallet-contracts`'s `upload_code`.
+#[derive(Debug, Encode, Decode, EncodeAsType)]
+#[encode_as_type(trait_bounds = "", crate_path = "subxt::ext::scale_encode")]
+pub struct UploadCode {
+    code: Vec<u8>,
+    storage_deposit_limit: Option<Balance>,
+    determinism: Determinism,
+}
+
+/// Whether the uploaded code may only be instantiated deterministically.
+///
+/// Copied from `pallet-contracts` to additionally implement `scale_encode::EncodeAsType`.
+#[derive(Debug, Encode, Decode, EncodeAsType)]
+#[encode_as_type(crate_path = "subxt::ext::scale_encode")]
+pub enum Determinism {
+    Enforced,
+    #[allow(unused)]
+    Relaxed,
+}
+
 /// Copied from `sp_weight` to additionally implement `scale_encode::EncodeAsType`.
 #[derive(
     Copy,
@@ -280,10 +486,9 @@ struct InstantiateRequest {
 /// Reference to an existing code hash or a new Wasm module.
 #[derive(Serialize, Encode)]
 #[serde(rename_all = "camelCase")]
-enum Code {
+pub enum Code {
     /// A Wasm module as raw bytes.
     Upload(Vec<u8>),
-    #[allow(unused)]
     /// The code hash of an on-chain Wasm blob.
     Existing(H256),
 }